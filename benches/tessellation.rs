@@ -0,0 +1,74 @@
+// Compares tessellating every circle every frame, which is what `new_circle` used to do, against
+// tessellating once and appending a cached `Mesh`. Run with `cargo bench`.
+
+use std::time::{Duration, Instant};
+
+use lyon::tessellation::VertexBuffers;
+
+use pumice::{Mesh, Paint, PumiceResult, Vertex};
+
+const CIRCLES: usize = 10_000;
+const FRAMES: u32 = 20;
+const RADIUS: f32 = 0.01;
+
+fn positions() -> Vec<[f32; 2]> {
+    (0..CIRCLES)
+        .map(|i| {
+            let t = i as f32 / CIRCLES as f32;
+            [t * 2.0 - 1.0, (t * 37.0).sin()]
+        })
+        .collect()
+}
+
+// average time per frame
+fn time_frames(
+    mut frame: impl FnMut(&mut VertexBuffers<Vertex, u16>) -> PumiceResult<()>,
+) -> Duration {
+    let mut geometry = VertexBuffers::new();
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        frame(&mut geometry).unwrap();
+    }
+    start.elapsed() / FRAMES
+}
+
+fn main() {
+    let positions = positions();
+    let paint = Paint::Solid([1.0, 0.0, 0.0, 1.0]);
+
+    let uncached = time_frames(|geometry| {
+        for pos in &positions {
+            // 16 bit indices only go so far, start over like a new frame would
+            if geometry.vertices.len() > 60_000 {
+                geometry.vertices.clear();
+                geometry.indices.clear();
+            }
+            Mesh::circle(RADIUS)?.append_to(geometry, *pos, 1.0, paint, None)?;
+        }
+        Ok(())
+    });
+
+    let mesh = Mesh::circle(RADIUS).unwrap();
+    let cached = time_frames(|geometry| {
+        for pos in &positions {
+            if geometry.vertices.len() > 60_000 {
+                geometry.vertices.clear();
+                geometry.indices.clear();
+            }
+            mesh.append_to(geometry, *pos, 1.0, paint, None)?;
+        }
+        Ok(())
+    });
+
+    println!(
+        "{} circles ({} vertices each), per frame:",
+        CIRCLES,
+        mesh.vertex_count()
+    );
+    println!("  tessellated every frame: {:?}", uncached);
+    println!("  cached mesh:             {:?}", cached);
+    println!(
+        "  speedup:                 {:.1}x",
+        uncached.as_secs_f64() / cached.as_secs_f64()
+    );
+}
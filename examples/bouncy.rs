@@ -1,6 +1,6 @@
 use pumice::winit;
-use pumice::GraphicsContext;
 use pumice::PumiceResult;
+use pumice::{ContextBuilder, Event, GraphicsContext, Paint};
 
 const RADIUS: f32 = 0.175;
 
@@ -21,7 +21,14 @@ fn update(ctx: &mut GraphicsContext, data: &mut Data) -> PumiceResult<()> {
         let win_size = window.get_inner_size().unwrap();
         ctx.screen_maxes = [(win_size.width / win_size.height) as f32, 1.0]
     }
-    ctx.new_circle([data.x, data.y], RADIUS, [1.0, 0.0, 0.0, 1.0])?;
+    // lit from the top left
+    let shading = Paint::radial(
+        [data.x - RADIUS / 3.0, data.y - RADIUS / 3.0],
+        RADIUS * 1.3,
+        [1.0, 0.5, 0.5, 1.0],
+        [0.6, 0.0, 0.0, 1.0],
+    );
+    ctx.new_circle([data.x, data.y], RADIUS, shading)?;
 
     if !data.paused {
         data.x += data.dx;
@@ -38,11 +45,11 @@ fn update(ctx: &mut GraphicsContext, data: &mut Data) -> PumiceResult<()> {
 }
 
 // Right now the winit events aren't preparsed in any way but I might change that
-fn handle_event(winit_event: &winit::Event, data: &mut Data) -> PumiceResult<()> {
-    if let winit::Event::DeviceEvent {
+fn handle_event(event: &Event, data: &mut Data) -> PumiceResult<()> {
+    if let Event::Winit(winit::Event::DeviceEvent {
         event: winit::DeviceEvent::Key(input),
         ..
-    } = winit_event
+    }) = event
     {
         let keycode = input.virtual_keycode;
         match keycode {
@@ -58,7 +65,11 @@ fn handle_event(winit_event: &winit::Event, data: &mut Data) -> PumiceResult<()>
 }
 
 fn main() -> PumiceResult<()> {
-    let ctx = GraphicsContext::new();
+    let ctx = ContextBuilder::new()
+        .title("bouncy")
+        .samples(4)
+        .clear_color([0.0, 0.0, 0.0, 1.0])
+        .try_build()?;
     let mut data = Data {
         x: 0.0,
         y: 0.0,
@@ -67,7 +78,6 @@ fn main() -> PumiceResult<()> {
         paused: false,
     };
 
-    // tell ctx the Data struct to use, the update function, event handling function,
-    // and clear color
-    ctx.run::<Data>(&mut data, &update, &handle_event, [0.0, 0.0, 0.0, 1.0])
+    // tell ctx the Data struct to use, the update function and event handling function
+    ctx.run::<Data>(&mut data, &update, &handle_event)
 }
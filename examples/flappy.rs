@@ -1,6 +1,7 @@
 use pumice::error::PumiceResult;
-use pumice::winit::{self, DeviceEvent, ElementState, Event, VirtualKeyCode, WindowEvent};
-use pumice::GraphicsContext;
+use pumice::gamepad::{Button, GamepadEvent};
+use pumice::winit::{self, DeviceEvent, ElementState, VirtualKeyCode};
+use pumice::{ContextBuilder, Easing, Event, GraphicsContext, Tween};
 
 extern crate rand;
 use rand::prelude::*;
@@ -50,7 +51,7 @@ struct Data {
     bird_x: f32,
     bird_y: f32,
     bird_vel: f32,
-    bird_rot: f32,
+    bird_rot: Tween<f32>,
     score: usize,
     pipes: [PipePair; 6],
     screen_size: [f32; 2],
@@ -62,7 +63,7 @@ impl Data {
             bird_x: -0.75,
             bird_y: 0.0,
             bird_vel: -0.02,
-            bird_rot: 0.0,
+            bird_rot: Tween::new(0.0),
             score: 0,
             pipes: PipePair::init(),
             screen_size: [1024.0, 1200.0],
@@ -81,7 +82,7 @@ fn update(ctx: &mut GraphicsContext, data: &mut Data) -> PumiceResult<()> {
     ctx.new_rectangle_rotcenter(
         [data.bird_x - BIRD_WIDTH / 2.0, data.bird_y],
         [BIRD_WIDTH, BIRD_HEIGHT],
-        data.bird_rot,
+        data.bird_rot.value(),
         [1.0, 0.0, 0.0, 1.0],
     )?;
 
@@ -140,45 +141,60 @@ fn update(ctx: &mut GraphicsContext, data: &mut Data) -> PumiceResult<()> {
     data.bird_vel += GRAVITY;
     data.bird_y += data.bird_vel;
 
-    let target_rot = (data.bird_vel * 420.0).min(42.0).max(-42.0);
-    data.bird_rot += (target_rot - data.bird_rot) * 0.5;
+    data.bird_rot.update(ctx.delta_time());
 
     Ok(())
 }
 
-fn handle_event(winit_event: &winit::Event, data: &mut Data) -> PumiceResult<()> {
-    match winit_event {
-        Event::DeviceEvent {
+fn jump(data: &mut Data) {
+    if data.bird_vel >= 0.00 {
+        data.bird_vel *= 0.5;
+    }
+
+    data.bird_vel += JUMP_VEL;
+
+    if data.bird_vel <= -0.00 {
+        data.bird_vel *= 0.5;
+    }
+
+    // tip up, hold for a moment, then nose dive
+    data.bird_rot = Tween::new(data.bird_rot.value())
+        .to(-30.0, 0.1, Easing::QuadOut)
+        .wait(0.25)
+        .to(42.0, 0.6, Easing::QuadIn);
+}
+
+fn handle_event(event: &Event, data: &mut Data) -> PumiceResult<()> {
+    match event {
+        Event::Winit(winit::Event::DeviceEvent {
             event: DeviceEvent::Key(input),
             ..
-        } => {
+        }) => {
             let keycode = input.virtual_keycode;
             match keycode {
                 Some(VirtualKeyCode::Space) | Some(VirtualKeyCode::Up) => {
                     if input.state == ElementState::Pressed {
-                        if data.bird_vel >= 0.00 {
-                            data.bird_vel *= 0.5;
-                        }
-
-                        data.bird_vel += JUMP_VEL;
-
-                        if data.bird_vel <= -0.00 {
-                            data.bird_vel *= 0.5;
-                        }
+                        jump(data);
                     }
                 }
                 _ => {}
             }
         }
+        Event::Gamepad(GamepadEvent::ButtonPressed(_, Button::South)) => jump(data),
         _ => {}
     }
     Ok(())
 }
 
 fn main() -> PumiceResult<()> {
-    let ctx = GraphicsContext::new();
+    let ctx = ContextBuilder::new()
+        .title("flappy")
+        .samples(4)
+        .clear_color([0.95, 0.95, 0.95, 1.0])
+        .alt_enter_fullscreen(true)
+        .try_build()?;
 
     let mut data = Data::new();
 
-    ctx.run::<Data>(&mut data, &update, &handle_event, [0.95, 0.95, 0.95, 1.0])
+    ctx.run::<Data>(&mut data, &update, &handle_event)
 }
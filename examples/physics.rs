@@ -0,0 +1,92 @@
+use pumice::physics::{Body, BodyHandle, Collider, World};
+use pumice::winit;
+use pumice::PumiceResult;
+use pumice::{ContextBuilder, Event, GraphicsContext};
+
+const WALL: f32 = 0.1;
+
+struct Data {
+    world: World,
+    // floor, left and right walls, moved to the window edges when it's resized
+    walls: [BodyHandle; 3],
+    spawn: bool,
+    spawned: u32,
+}
+
+fn update(ctx: &mut GraphicsContext, data: &mut Data) -> PumiceResult<()> {
+    if ctx.screen_size_changed {
+        let window = ctx.surface.window();
+        let win_size = window.get_inner_size().unwrap();
+        ctx.screen_maxes = [(win_size.width / win_size.height) as f32, 1.0];
+
+        let maxes = ctx.screen_maxes;
+        let positions = [
+            [0.0, 1.0 + WALL / 2.0],
+            [-maxes[0] - WALL / 2.0, 0.0],
+            [maxes[0] + WALL / 2.0, 0.0],
+        ];
+        for (&wall, &position) in data.walls.iter().zip(positions.iter()) {
+            if let Some(body) = data.world.body_mut(wall) {
+                body.position = position;
+            }
+        }
+    }
+
+    if data.spawn {
+        data.spawn = false;
+        data.spawned += 1;
+        let x = (data.spawned as f32 * 0.37).sin() * 0.5;
+        let collider = match data.spawned % 3 {
+            0 => Collider::Circle(0.08),
+            1 => Collider::Rectangle([0.2, 0.12]),
+            _ => Collider::Polygon(vec![[0.0, -0.1], [0.1, 0.08], [-0.1, 0.08]]),
+        };
+        let mut body = Body::dynamic([x, -0.8], collider).restitution(0.6);
+        body.angle = data.spawned as f32 * 25.0;
+        data.world.add(body);
+    }
+
+    data.world.step(ctx.delta_time());
+    data.world.debug_draw(ctx, 0.01, [1.0, 1.0, 1.0, 1.0])
+}
+
+fn handle_event(event: &Event, data: &mut Data) -> PumiceResult<()> {
+    if let Event::Winit(winit::Event::DeviceEvent {
+        event: winit::DeviceEvent::Key(input),
+        ..
+    }) = event
+    {
+        if input.virtual_keycode == Some(winit::VirtualKeyCode::Space)
+            && input.state == winit::ElementState::Pressed
+        {
+            data.spawn = true;
+        }
+    }
+    Ok(())
+}
+
+fn main() -> PumiceResult<()> {
+    let ctx = ContextBuilder::new()
+        .title("physics")
+        .samples(4)
+        .clear_color([0.0, 0.0, 0.0, 1.0])
+        .try_build()?;
+
+    let mut world = World::new([0.0, 2.0]);
+    let walls = [
+        world.add(Body::fixed([0.0, 1.05], Collider::Rectangle([10.0, WALL]))),
+        world.add(Body::fixed([-1.05, 0.0], Collider::Rectangle([WALL, 10.0]))),
+        world.add(Body::fixed([1.05, 0.0], Collider::Rectangle([WALL, 10.0]))),
+    ];
+    world.add(Body::dynamic([0.0, 0.0], Collider::Circle(0.175)).restitution(0.9));
+
+    // space drops another shape
+    let mut data = Data {
+        world,
+        walls,
+        spawn: false,
+        spawned: 0,
+    };
+
+    ctx.run::<Data>(&mut data, &update, &handle_event)
+}
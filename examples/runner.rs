@@ -1,6 +1,7 @@
+use pumice::collision::{Aabb, Shape};
 use pumice::winit;
-use pumice::GraphicsContext;
 use pumice::PumiceResult;
+use pumice::{Animation, Clip, ContextBuilder, Event, GraphicsContext, PlayMode, Rotation};
 
 extern crate rand;
 use rand::prelude::*;
@@ -16,6 +17,7 @@ const DINO_HEIGHT: f32 = 0.275;
 
 const DINO_DUCK_HEIGHT: f32 = 0.1;
 const DINO_DUCK_WIDTH: f32 = 0.2;
+const LEG_HEIGHT: f32 = 0.04;
 
 const DINO_JUMP_SPEED: f32 = -0.05;
 const GRAVITY: f32 = 0.005;
@@ -90,6 +92,7 @@ struct Data {
     speed: f32,
     obstacles: [Obstacle; 12],
     duck_held: bool,
+    animation: Animation,
 }
 
 fn update(ctx: &mut GraphicsContext, data: &mut Data) -> PumiceResult<()> {
@@ -110,17 +113,13 @@ fn update(ctx: &mut GraphicsContext, data: &mut Data) -> PumiceResult<()> {
             DinoState::Air(y, _) => (y, DINO_HEIGHT, DINO_WIDTH),
         };
 
-        let dino_center_x = data.dino_x + dino_width / 2.0;
-        let dino_center_y = dino_y - dino_height / 2.0;
-
-        let obstacle_center_x = obstacle.x + 0.05;
-        let obstacle_center_y = obstacle.y + 0.05;
+        let dino = Shape::from(Aabb::new(
+            [data.dino_x, dino_y - dino_height],
+            [dino_width, dino_height],
+        ));
+        let obstacle = Shape::from(Aabb::new([obstacle.x, obstacle.y], [0.1, 0.1]));
 
-        if dino_center_x - dino_width / 2.0 < obstacle_center_x + 0.05
-            && dino_center_x + dino_width / 2.0 > obstacle_center_x - 0.05
-            && dino_center_y - dino_height / 2.0 < obstacle_center_y + 0.05
-            && dino_center_y + dino_height / 2.0 > obstacle_center_y - 0.05
-        {
+        if dino.overlaps(&obstacle) {
             println!("You Died! Speed: {}", data.speed);
             std::process::exit(0);
         }
@@ -164,6 +163,13 @@ fn update(ctx: &mut GraphicsContext, data: &mut Data) -> PumiceResult<()> {
         data.dino = DinoState::Ground;
     }
 
+    data.animation.play(match data.dino {
+        DinoState::Ground => "run",
+        DinoState::Duck => "duck",
+        DinoState::Air(..) => "jump",
+    });
+    data.animation.update(ctx.delta_time());
+
     //dino
     {
         let (dino_y, dino_width, dino_height, mut target_rot) = match data.dino {
@@ -189,12 +195,38 @@ fn update(ctx: &mut GraphicsContext, data: &mut Data) -> PumiceResult<()> {
         }
         data.dino_rot += (target_rot - data.dino_rot) * 0.35;
 
-        ctx.new_rectangle_rotcenter(
+        let rot = Some(Rotation {
+            degrees: data.dino_rot,
+            point: [data.dino_x + dino_width / 2.0, dino_y + dino_height / 2.0],
+        });
+        // the body stops short of the ground to leave room for the legs
+        ctx.new_rectangle_full(
             [data.dino_x, dino_y],
-            [dino_width, dino_height],
-            data.dino_rot,
+            [dino_width, dino_height - LEG_HEIGHT],
             [0.0, 0.0, 0.0, 1.0],
-        );
+            rot,
+        )?;
+
+        // there are no sprites to draw yet, so the animation frame picks which leg is lifted
+        let lifted = match data.dino {
+            DinoState::Air(..) => [true, true],
+            _ => {
+                let frame = data.animation.frame_index();
+                [frame == 0, frame == 1]
+            }
+        };
+        for (i, &lifted) in lifted.iter().enumerate() {
+            let leg_height = if lifted { LEG_HEIGHT / 2.0 } else { LEG_HEIGHT };
+            ctx.new_rectangle_full(
+                [
+                    data.dino_x + dino_width * (0.15 + 0.45 * i as f32),
+                    dino_y + dino_height - LEG_HEIGHT,
+                ],
+                [dino_width * 0.25, leg_height],
+                [0.0, 0.0, 0.0, 1.0],
+                rot,
+            )?;
+        }
     }
     ctx.new_rectangle(
         [-ctx.screen_maxes[0], GROUND_Y],
@@ -224,11 +256,11 @@ fn update(ctx: &mut GraphicsContext, data: &mut Data) -> PumiceResult<()> {
     Ok(())
 }
 
-fn handle_event(winit_event: &winit::Event, data: &mut Data) -> PumiceResult<()> {
-    if let winit::Event::DeviceEvent {
+fn handle_event(event: &Event, data: &mut Data) -> PumiceResult<()> {
+    if let Event::Winit(winit::Event::DeviceEvent {
         event: winit::DeviceEvent::Key(input),
         ..
-    } = winit_event
+    }) = event
     {
         use winit::VirtualKeyCode;
 
@@ -257,7 +289,11 @@ fn handle_event(winit_event: &winit::Event, data: &mut Data) -> PumiceResult<()>
 }
 
 fn main() -> PumiceResult<()> {
-    let ctx = GraphicsContext::new();
+    let ctx = ContextBuilder::new()
+        .title("runner")
+        .clear_color([0.95, 0.95, 0.95, 1.0])
+        .alt_enter_fullscreen(true)
+        .try_build()?;
     let mut data = Data {
         dino: DinoState::Ground,
         dino_x: -1.5,
@@ -267,7 +303,20 @@ fn main() -> PumiceResult<()> {
         speed: START_SPEED,
         obstacles: Obstacle::init(),
         duck_held: false,
+        animation: Animation::new()
+            .clip(
+                "run",
+                Clip::from_row(PlayMode::Loop, [0.0, 0.0], [64.0, 64.0], 2, 0.1),
+            )
+            .clip(
+                "duck",
+                Clip::from_row(PlayMode::Loop, [0.0, 64.0], [64.0, 32.0], 2, 0.08),
+            )
+            .clip(
+                "jump",
+                Clip::from_row(PlayMode::Once, [0.0, 96.0], [64.0, 64.0], 1, 0.1),
+            ),
     };
 
-    ctx.run::<Data>(&mut data, &update, &handle_event, [0.95, 0.95, 0.95, 1.0])
+    ctx.run::<Data>(&mut data, &update, &handle_event)
 }
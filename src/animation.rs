@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+
+/// What a clip does when it reaches its last frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PlayMode {
+    // stops on the last frame
+    Once,
+    Loop,
+    // runs backwards to the first frame, then forwards again
+    PingPong,
+}
+
+/// One frame of a sprite sheet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnimationFrame {
+    // [x, y, width, height] on the sheet, in whatever units the sheet is drawn with
+    pub rect: [f32; 4],
+    // seconds
+    pub duration: f32,
+    // reported by `Animation::update` when the frame comes up, e.g. "footstep"
+    pub event: Option<String>,
+}
+
+/// A named sequence of frames, like "run" or "jump".
+#[derive(Clone, Debug)]
+pub struct Clip {
+    frames: Vec<AnimationFrame>,
+    mode: PlayMode,
+}
+
+impl Clip {
+    pub fn new(mode: PlayMode) -> Self {
+        Clip {
+            frames: Vec::new(),
+            mode,
+        }
+    }
+
+    // `count` frames of `size` laid out left to right on a sheet, starting at `origin`
+    pub fn from_row(
+        mode: PlayMode,
+        origin: [f32; 2],
+        size: [f32; 2],
+        count: usize,
+        duration: f32,
+    ) -> Self {
+        (0..count).fold(Clip::new(mode), |clip, i| {
+            clip.frame(
+                [origin[0] + size[0] * i as f32, origin[1], size[0], size[1]],
+                duration,
+            )
+        })
+    }
+
+    pub fn frame(mut self, rect: [f32; 4], duration: f32) -> Self {
+        self.frames.push(AnimationFrame {
+            rect,
+            duration,
+            event: None,
+        });
+        self
+    }
+
+    pub fn frame_with_event(mut self, rect: [f32; 4], duration: f32, event: &str) -> Self {
+        self.frames.push(AnimationFrame {
+            rect,
+            duration,
+            event: Some(event.to_string()),
+        });
+        self
+    }
+
+    // sets the event on a frame that's already been added, e.g. one made by `from_row`
+    pub fn event(mut self, frame: usize, event: &str) -> Self {
+        if let Some(frame) = self.frames.get_mut(frame) {
+            frame.event = Some(event.to_string());
+        }
+        self
+    }
+
+    pub fn frames(&self) -> &[AnimationFrame] {
+        &self.frames
+    }
+
+    pub fn mode(&self) -> PlayMode {
+        self.mode
+    }
+
+    // one pass through the frames, in seconds
+    pub fn duration(&self) -> f32 {
+        self.frames.iter().map(|frame| frame.duration).sum()
+    }
+}
+
+/// Steps through the frames of whichever clip is playing. Call `update` with `ctx.delta_time()`
+/// every frame and draw `frame().rect` from the sprite sheet.
+///
+/// The renderer can't draw textures yet, so for now this only keeps time; the rects are there for
+/// when sprites land.
+#[derive(Clone, Debug, Default)]
+pub struct Animation {
+    clips: HashMap<String, Clip>,
+    current: Option<String>,
+    frame: usize,
+    // seconds into the current frame
+    time: f32,
+    // 1 or -1, only ever -1 while a ping pong clip runs backwards
+    direction: isize,
+    finished: bool,
+    // the current frame hasn't had its event reported yet
+    entered: bool,
+    events: Vec<String>,
+}
+
+impl Animation {
+    pub fn new() -> Self {
+        Animation {
+            direction: 1,
+            ..Default::default()
+        }
+    }
+
+    // adds a clip, the first one added starts playing
+    pub fn clip(mut self, name: &str, clip: Clip) -> Self {
+        self.clips.insert(name.to_string(), clip);
+        if self.current.is_none() {
+            self.play(name);
+        }
+        self
+    }
+
+    // Switches to the clip called `name` from its first frame. Playing the clip that's already
+    // playing does nothing, so this can be called every frame with the state the game is in.
+    pub fn play(&mut self, name: &str) {
+        if self.current.as_deref() == Some(name) {
+            return;
+        }
+        if self.clips.contains_key(name) {
+            self.current = Some(name.to_string());
+            self.restart();
+        } else {
+            log::warn!("no animation clip called {:?}", name);
+        }
+    }
+
+    pub fn restart(&mut self) {
+        self.frame = 0;
+        self.time = 0.0;
+        self.direction = 1;
+        self.finished = false;
+        self.entered = true;
+    }
+
+    pub fn current_clip(&self) -> Option<&str> {
+        self.current.as_deref()
+    }
+
+    fn clip_ref(&self) -> Option<&Clip> {
+        self.current.as_ref().and_then(|name| self.clips.get(name))
+    }
+
+    pub fn frame(&self) -> Option<&AnimationFrame> {
+        self.clip_ref().and_then(|clip| clip.frames.get(self.frame))
+    }
+
+    pub fn frame_index(&self) -> usize {
+        self.frame
+    }
+
+    // true once a `PlayMode::Once` clip has shown its last frame for its full duration
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    // Advances by `dt` seconds and returns the events of every frame that came up, in order.
+    // Frames can be skipped on a slow frame, but their events are still reported.
+    pub fn update(&mut self, dt: f32) -> &[String] {
+        self.events.clear();
+        let clips = &self.clips;
+        let clip = match self.current.as_ref().and_then(|name| clips.get(name)) {
+            Some(clip) if !clip.frames.is_empty() => clip,
+            _ => return &self.events,
+        };
+
+        if self.entered {
+            self.entered = false;
+            self.events.extend(clip.frames[self.frame].event.clone());
+        }
+        if self.finished {
+            return &self.events;
+        }
+        // a clip with no length would never get through its frames
+        if clip.duration() <= 0.0 {
+            self.finished = true;
+            return &self.events;
+        }
+
+        self.time += dt;
+        while self.time >= clip.frames[self.frame].duration {
+            self.time -= clip.frames[self.frame].duration;
+
+            let last = clip.frames.len() - 1;
+            let next = match clip.mode {
+                PlayMode::Once if self.frame == last => {
+                    self.finished = true;
+                    self.time = 0.0;
+                    break;
+                }
+                PlayMode::Loop if self.frame == last => 0,
+                PlayMode::PingPong if last == 0 => 0,
+                PlayMode::PingPong => {
+                    let next = self.frame as isize + self.direction;
+                    if next < 0 || next > last as isize {
+                        self.direction = -self.direction;
+                    }
+                    (self.frame as isize + self.direction) as usize
+                }
+                _ => self.frame + 1,
+            };
+
+            self.frame = next;
+            self.events.extend(clip.frames[next].event.clone());
+        }
+
+        &self.events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // three 1 second frames with an event on each
+    fn clip(mode: PlayMode) -> Clip {
+        Clip::from_row(mode, [0.0, 0.0], [16.0, 16.0], 3, 1.0)
+            .event(0, "a")
+            .event(1, "b")
+            .event(2, "c")
+    }
+
+    fn frames(animation: &mut Animation, steps: usize) -> Vec<usize> {
+        (0..steps)
+            .map(|_| {
+                animation.update(1.0);
+                animation.frame_index()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn once_stops_on_the_last_frame() {
+        let mut animation = Animation::new().clip("once", clip(PlayMode::Once));
+        assert_eq!(frames(&mut animation, 4), vec![1, 2, 2, 2]);
+        assert!(animation.is_finished());
+        assert_eq!(animation.frame().unwrap().rect, [32.0, 0.0, 16.0, 16.0]);
+    }
+
+    #[test]
+    fn loop_wraps_to_the_first_frame() {
+        let mut animation = Animation::new().clip("loop", clip(PlayMode::Loop));
+        assert_eq!(frames(&mut animation, 5), vec![1, 2, 0, 1, 2]);
+        assert!(!animation.is_finished());
+    }
+
+    #[test]
+    fn ping_pong_runs_back_and_forth() {
+        let mut animation = Animation::new().clip("ping pong", clip(PlayMode::PingPong));
+        assert_eq!(frames(&mut animation, 6), vec![1, 2, 1, 0, 1, 2]);
+
+        // a single frame clip stays put
+        let mut animation = Animation::new().clip(
+            "still",
+            Clip::from_row(PlayMode::PingPong, [0.0, 0.0], [16.0, 16.0], 1, 1.0),
+        );
+        assert_eq!(frames(&mut animation, 3), vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn partial_frames_carry_over() {
+        let mut animation = Animation::new().clip("loop", clip(PlayMode::Loop));
+        animation.update(0.6);
+        assert_eq!(animation.frame_index(), 0);
+        animation.update(0.6);
+        assert_eq!(animation.frame_index(), 1);
+    }
+
+    #[test]
+    fn first_frame_event_is_reported_on_the_first_update() {
+        let mut animation = Animation::new().clip("loop", clip(PlayMode::Loop));
+        assert_eq!(animation.update(0.0), ["a"]);
+        assert!(animation.update(0.0).is_empty());
+    }
+
+    #[test]
+    fn skipped_frames_still_report_their_events() {
+        let mut animation = Animation::new().clip("loop", clip(PlayMode::Loop));
+        animation.update(0.0);
+        // one slow update passes frames 1 and 2 and lands back on 0
+        assert_eq!(animation.update(3.5), ["b", "c", "a"]);
+        assert_eq!(animation.frame_index(), 0);
+
+        let mut animation = Animation::new().clip("ping pong", clip(PlayMode::PingPong));
+        animation.update(0.0);
+        assert_eq!(animation.update(4.0), ["b", "c", "b", "a"]);
+    }
+
+    #[test]
+    fn play_switches_clips_and_ignores_the_current_one() {
+        let mut animation = Animation::new()
+            .clip("loop", clip(PlayMode::Loop))
+            .clip("once", clip(PlayMode::Once));
+        assert_eq!(animation.current_clip(), Some("loop"));
+        animation.update(1.0);
+
+        animation.play("loop");
+        assert_eq!(animation.frame_index(), 1);
+
+        animation.play("once");
+        assert_eq!(animation.current_clip(), Some("once"));
+        assert_eq!(animation.frame_index(), 0);
+        assert_eq!(animation.update(0.0), ["a"]);
+
+        // unknown clips keep the current one playing
+        animation.play("missing");
+        assert_eq!(animation.current_clip(), Some("once"));
+    }
+}
@@ -0,0 +1,246 @@
+use std::path::Path;
+
+use vulkano::swapchain::PresentMode;
+
+use winit::dpi::LogicalSize;
+use winit::{EventsLoop, Icon, WindowBuilder};
+
+use crate::debug::DebugOptions;
+use crate::device::DevicePreference;
+use crate::error::{PumiceError, PumiceResult};
+use crate::mesh::TessellationQuality;
+use crate::report::{ErrorAction, ErrorHandler, ErrorOrigin};
+use crate::{DeviceRecreatedHook, GraphicsContext};
+
+/// Options for the window and renderer that have to be known before `GraphicsContext` is created.
+pub struct ContextBuilder {
+    pub(crate) title: String,
+    pub(crate) dimensions: Option<LogicalSize>,
+    pub(crate) min_dimensions: Option<LogicalSize>,
+    pub(crate) max_dimensions: Option<LogicalSize>,
+    pub(crate) resizable: bool,
+    pub(crate) fullscreen: bool,
+    pub(crate) decorations: bool,
+    pub(crate) icon: Option<Icon>,
+    pub(crate) present_mode: PresentMode,
+    pub(crate) samples: u32,
+    pub(crate) clear_color: [f32; 4],
+    pub(crate) alt_enter_fullscreen: bool,
+    pub(crate) target_fps: Option<f64>,
+    pub(crate) device_preference: DevicePreference,
+    pub(crate) debug: Option<DebugOptions>,
+    pub(crate) error_handler: Option<ErrorHandler>,
+    pub(crate) device_recreated_hook: Option<DeviceRecreatedHook>,
+    pub(crate) stats_overlay: bool,
+    pub(crate) feathered_edges: bool,
+    pub(crate) tessellation_quality: TessellationQuality,
+}
+
+impl ContextBuilder {
+    pub fn new() -> Self {
+        ContextBuilder {
+            title: "pumice".to_string(),
+            dimensions: None,
+            min_dimensions: None,
+            max_dimensions: None,
+            resizable: true,
+            fullscreen: false,
+            decorations: true,
+            icon: None,
+            present_mode: PresentMode::Fifo,
+            samples: 1,
+            clear_color: [0.0, 0.0, 0.0, 1.0],
+            alt_enter_fullscreen: false,
+            target_fps: None,
+            device_preference: DevicePreference::Default,
+            debug: None,
+            error_handler: None,
+            device_recreated_hook: None,
+            stats_overlay: false,
+            feathered_edges: false,
+            tessellation_quality: TessellationQuality::default(),
+        }
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    pub fn dimensions(mut self, width: f64, height: f64) -> Self {
+        self.dimensions = Some(LogicalSize::new(width, height));
+        self
+    }
+
+    pub fn min_dimensions(mut self, width: f64, height: f64) -> Self {
+        self.min_dimensions = Some(LogicalSize::new(width, height));
+        self
+    }
+
+    pub fn max_dimensions(mut self, width: f64, height: f64) -> Self {
+        self.max_dimensions = Some(LogicalSize::new(width, height));
+        self
+    }
+
+    pub fn resizable(mut self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
+
+    // fullscreen on the primary monitor
+    pub fn fullscreen(mut self, fullscreen: bool) -> Self {
+        self.fullscreen = fullscreen;
+        self
+    }
+
+    pub fn decorations(mut self, decorations: bool) -> Self {
+        self.decorations = decorations;
+        self
+    }
+
+    pub fn icon(mut self, icon: Icon) -> Self {
+        self.icon = Some(icon);
+        self
+    }
+
+    pub fn present_mode(mut self, present_mode: PresentMode) -> Self {
+        self.present_mode = present_mode;
+        self
+    }
+
+    // caps the frame rate on the CPU, mostly useful with present modes that don't wait for vsync
+    pub fn target_fps(mut self, target_fps: f64) -> Self {
+        self.target_fps = Some(target_fps);
+        self
+    }
+
+    // MSAA sample count, 1 disables multisampling. Usually 2, 4 or 8, it's lowered to whatever the
+    // device supports.
+    pub fn samples(mut self, samples: u32) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    // Fades the edges of every shape out over a pixel so they look smooth without MSAA. Much
+    // cheaper than MSAA on software renderers like lavapipe.
+    pub fn feathered_edges(mut self, enabled: bool) -> Self {
+        self.feathered_edges = enabled;
+        self
+    }
+
+    // how finely curves are tessellated, by default it adapts to the window size and zoom
+    pub fn tessellation_quality(mut self, quality: TessellationQuality) -> Self {
+        self.tessellation_quality = quality;
+        self
+    }
+
+    pub fn clear_color(mut self, clear_color: [f32; 4]) -> Self {
+        self.clear_color = clear_color;
+        self
+    }
+
+    // let Alt+Enter toggle fullscreen, see GraphicsContext::toggle_fullscreen
+    pub fn alt_enter_fullscreen(mut self, enabled: bool) -> Self {
+        self.alt_enter_fullscreen = enabled;
+        self
+    }
+
+    // shows the FPS and a frame time graph in the top left corner, see GraphicsContext::stats
+    pub fn stats_overlay(mut self, enabled: bool) -> Self {
+        self.stats_overlay = enabled;
+        self
+    }
+
+    // which GPU to use, the PUMICE_DEVICE environment variable takes priority over this
+    pub fn device_preference(mut self, device_preference: DevicePreference) -> Self {
+        self.device_preference = device_preference;
+        self
+    }
+
+    // enables the Vulkan validation layer, with messages logged under the "vulkan" target
+    pub fn debug(mut self, enabled: bool) -> Self {
+        self.debug = if enabled {
+            Some(DebugOptions::new())
+        } else {
+            None
+        };
+        self
+    }
+
+    // enables the Vulkan validation layer with a custom logger and severity filter
+    pub fn debug_options(mut self, options: DebugOptions) -> Self {
+        self.debug = Some(options);
+        self
+    }
+
+    // called with errors from update, handle_event and rendering instead of logging them, and
+    // decides whether `run` keeps going
+    pub fn error_handler(
+        mut self,
+        handler: impl FnMut(ErrorOrigin, &PumiceError, u32) -> ErrorAction + 'static,
+    ) -> Self {
+        self.error_handler = Some(Box::new(handler));
+        self
+    }
+
+    // Called by `run` after a lost device has been replaced, before the next frame is drawn. Use
+    // it to rebuild anything the game made on the old device.
+    pub fn on_device_recreated(mut self, hook: impl FnMut(&mut GraphicsContext) + 'static) -> Self {
+        self.device_recreated_hook = Some(Box::new(hook));
+        self
+    }
+
+    pub fn build(self) -> GraphicsContext {
+        match self.try_build() {
+            Ok(ctx) => ctx,
+            Err(e) => panic!("Error creating GraphicsContext: {}", e),
+        }
+    }
+
+    pub fn try_build(self) -> PumiceResult<GraphicsContext> {
+        GraphicsContext::from_builder(self)
+    }
+
+    pub(crate) fn window_builder(&mut self, events_loop: &EventsLoop) -> WindowBuilder {
+        let mut window_builder = WindowBuilder::new()
+            .with_title(self.title.clone())
+            .with_resizable(self.resizable)
+            .with_decorations(self.decorations)
+            .with_window_icon(self.icon.take());
+
+        if let Some(dimensions) = self.dimensions {
+            window_builder = window_builder.with_dimensions(dimensions);
+        }
+        if let Some(min_dimensions) = self.min_dimensions {
+            window_builder = window_builder.with_min_dimensions(min_dimensions);
+        }
+        if let Some(max_dimensions) = self.max_dimensions {
+            window_builder = window_builder.with_max_dimensions(max_dimensions);
+        }
+        if self.fullscreen {
+            window_builder =
+                window_builder.with_fullscreen(Some(events_loop.get_primary_monitor()));
+        }
+
+        window_builder
+    }
+}
+
+// loads a window icon from any image format the `image` crate can read
+pub fn load_icon(path: impl AsRef<Path>) -> PumiceResult<Icon> {
+    let path = path.as_ref();
+    let image = match image::open(path) {
+        Ok(image) => image.to_rgba(),
+        Err(e) => return Err(PumiceError::Asset(format!("{}: {}", path.display(), e))),
+    };
+
+    let (width, height) = image.dimensions();
+    Icon::from_rgba(image.into_raw(), width, height)
+        .map_err(|e| PumiceError::Asset(format!("{}: {:?}", path.display(), e)))
+}
+
+impl Default for ContextBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
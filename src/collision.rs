@@ -0,0 +1,638 @@
+use crate::Rotation;
+
+fn add(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] + b[0], a[1] + b[1]]
+}
+
+fn sub(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn scale(a: [f32; 2], s: f32) -> [f32; 2] {
+    [a[0] * s, a[1] * s]
+}
+
+fn dot(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[0] + a[1] * b[1]
+}
+
+fn cross(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[1] - a[1] * b[0]
+}
+
+fn length(a: [f32; 2]) -> f32 {
+    dot(a, a).sqrt()
+}
+
+fn normalize(a: [f32; 2]) -> Option<[f32; 2]> {
+    let len = length(a);
+    if len > 0.0 {
+        Some(scale(a, 1.0 / len))
+    } else {
+        None
+    }
+}
+
+/// An axis aligned rectangle, laid out like `new_rectangle`: `pos` is the corner with the smallest
+/// coordinates, the top left with y pointing down.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+impl Aabb {
+    pub fn new(pos: [f32; 2], sides: [f32; 2]) -> Self {
+        Aabb {
+            min: pos,
+            max: add(pos, sides),
+        }
+    }
+
+    pub fn center(&self) -> [f32; 2] {
+        scale(add(self.min, self.max), 0.5)
+    }
+
+    fn corners(&self) -> Vec<[f32; 2]> {
+        vec![
+            self.min,
+            [self.max[0], self.min[1]],
+            self.max,
+            [self.min[0], self.max[1]],
+        ]
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Circle {
+    pub center: [f32; 2],
+    pub radius: f32,
+}
+
+impl Circle {
+    pub fn new(center: [f32; 2], radius: f32) -> Self {
+        Circle { center, radius }
+    }
+}
+
+/// A rectangle turned by a `Rotation`, the same one `new_rectangle_full` draws with the same
+/// arguments.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct OrientedRect {
+    pub pos: [f32; 2],
+    pub sides: [f32; 2],
+    pub rot: Rotation,
+}
+
+impl OrientedRect {
+    pub fn new(pos: [f32; 2], sides: [f32; 2], rot: Rotation) -> Self {
+        OrientedRect { pos, sides, rot }
+    }
+
+    // turned around its center, like `new_rectangle_rotcenter`
+    pub fn rotcenter(pos: [f32; 2], sides: [f32; 2], degrees: f32) -> Self {
+        OrientedRect {
+            pos,
+            sides,
+            rot: Rotation {
+                degrees,
+                point: add(pos, scale(sides, 0.5)),
+            },
+        }
+    }
+
+    pub fn corners(&self) -> [[f32; 2]; 4] {
+        let (sin, cos) = self.rot.degrees.to_radians().sin_cos();
+        let turn = |p: [f32; 2]| {
+            let d = sub(p, self.rot.point);
+            add(
+                [cos * d[0] - sin * d[1], sin * d[0] + cos * d[1]],
+                self.rot.point,
+            )
+        };
+        let aabb = Aabb::new(self.pos, self.sides);
+        let corners = aabb.corners();
+        [
+            turn(corners[0]),
+            turn(corners[1]),
+            turn(corners[2]),
+            turn(corners[3]),
+        ]
+    }
+}
+
+/// A convex polygon. The points can go either way round, but concave polygons will give wrong
+/// answers; split them into convex pieces first. One with fewer than 3 points is empty: it contains
+/// nothing, overlaps nothing and no ray hits it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Polygon {
+    pub points: Vec<[f32; 2]>,
+}
+
+impl Polygon {
+    pub fn new(points: Vec<[f32; 2]>) -> Self {
+        Polygon { points }
+    }
+}
+
+/// Any of the shapes, so they can be tested against each other without a function for every
+/// pair.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+    Aabb(Aabb),
+    Circle(Circle),
+    OrientedRect(OrientedRect),
+    Polygon(Polygon),
+}
+
+impl From<Aabb> for Shape {
+    fn from(aabb: Aabb) -> Self {
+        Shape::Aabb(aabb)
+    }
+}
+
+impl From<Circle> for Shape {
+    fn from(circle: Circle) -> Self {
+        Shape::Circle(circle)
+    }
+}
+
+impl From<OrientedRect> for Shape {
+    fn from(rect: OrientedRect) -> Self {
+        Shape::OrientedRect(rect)
+    }
+}
+
+impl From<Polygon> for Shape {
+    fn from(polygon: Polygon) -> Self {
+        Shape::Polygon(polygon)
+    }
+}
+
+/// How two overlapping shapes overlap. Moving the first shape by `-normal * depth`, or the second
+/// by `normal * depth`, just separates them.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Contact {
+    // unit length, pointing from the first shape towards the second
+    pub normal: [f32; 2],
+    pub depth: f32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RayHit {
+    // along the ray, in game units
+    pub distance: f32,
+    pub point: [f32; 2],
+    // the surface normal where the ray hit, pointing out of the shape
+    pub normal: [f32; 2],
+}
+
+// everything but circles is tested as a convex polygon
+enum Convex {
+    Circle(Circle),
+    // points, and the outward normal of the edge from each point to the next
+    Polygon(Vec<[f32; 2]>, Vec<[f32; 2]>),
+}
+
+fn polygon(points: Vec<[f32; 2]>) -> Convex {
+    let area = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(&a, &b)| cross(a, b))
+        .sum::<f32>();
+    let normals = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(&a, &b)| {
+            let edge = sub(b, a);
+            let normal = if area > 0.0 {
+                [edge[1], -edge[0]]
+            } else {
+                [-edge[1], edge[0]]
+            };
+            normalize(normal).unwrap_or([0.0, 0.0])
+        })
+        .collect();
+    Convex::Polygon(points, normals)
+}
+
+impl Shape {
+    fn is_empty(&self) -> bool {
+        match self {
+            Shape::Polygon(polygon) => polygon.points.len() < 3,
+            _ => false,
+        }
+    }
+
+    fn convex(&self) -> Convex {
+        match self {
+            Shape::Aabb(aabb) => polygon(aabb.corners()),
+            Shape::Circle(circle) => Convex::Circle(*circle),
+            Shape::OrientedRect(rect) => polygon(rect.corners().to_vec()),
+            Shape::Polygon(polygon_shape) => polygon(polygon_shape.points.clone()),
+        }
+    }
+
+    // the smallest Aabb around the shape, for `SpatialHash`
+    pub fn bounds(&self) -> Aabb {
+        match self.convex() {
+            Convex::Circle(circle) => Aabb {
+                min: sub(circle.center, [circle.radius, circle.radius]),
+                max: add(circle.center, [circle.radius, circle.radius]),
+            },
+            Convex::Polygon(points, _) => points.iter().fold(
+                Aabb {
+                    min: [f32::INFINITY; 2],
+                    max: [f32::NEG_INFINITY; 2],
+                },
+                |aabb, p| Aabb {
+                    min: [aabb.min[0].min(p[0]), aabb.min[1].min(p[1])],
+                    max: [aabb.max[0].max(p[0]), aabb.max[1].max(p[1])],
+                },
+            ),
+        }
+    }
+
+    pub fn center(&self) -> [f32; 2] {
+        match self {
+            Shape::Aabb(aabb) => aabb.center(),
+            Shape::Circle(circle) => circle.center,
+            Shape::OrientedRect(rect) => {
+                let corners = rect.corners();
+                scale(add(corners[0], corners[2]), 0.5)
+            }
+            Shape::Polygon(polygon) => {
+                let sum = polygon
+                    .points
+                    .iter()
+                    .fold([0.0, 0.0], |acc, &p| add(acc, p));
+                scale(sum, 1.0 / polygon.points.len().max(1) as f32)
+            }
+        }
+    }
+
+    pub fn contains(&self, point: [f32; 2]) -> bool {
+        if self.is_empty() {
+            return false;
+        }
+        match self.convex() {
+            Convex::Circle(circle) => length(sub(point, circle.center)) <= circle.radius,
+            Convex::Polygon(points, normals) => points
+                .iter()
+                .zip(normals.iter())
+                .all(|(&p, &n)| dot(sub(point, p), n) <= 0.0),
+        }
+    }
+
+    pub fn overlaps(&self, other: &Shape) -> bool {
+        self.contact(other).is_some()
+    }
+
+    // None if the shapes don't overlap; shapes that only touch don't count
+    pub fn contact(&self, other: &Shape) -> Option<Contact> {
+        if self.is_empty() || other.is_empty() {
+            return None;
+        }
+        let a = self.convex();
+        let b = other.convex();
+        let between = sub(other.center(), self.center());
+
+        let mut axes = Vec::new();
+        match (&a, &b) {
+            (Convex::Circle(a), Convex::Circle(b)) => {
+                let d = sub(b.center, a.center);
+                let distance = length(d);
+                let depth = a.radius + b.radius - distance;
+                if depth <= 0.0 {
+                    return None;
+                }
+                // concentric circles can be pushed apart any way, so pick one
+                let normal = normalize(d).unwrap_or([1.0, 0.0]);
+                return Some(Contact { normal, depth });
+            }
+            (Convex::Polygon(points, normals), Convex::Circle(circle))
+            | (Convex::Circle(circle), Convex::Polygon(points, normals)) => {
+                axes.extend_from_slice(normals);
+                // the normals alone miss a circle sitting off a corner
+                let closest = points.iter().min_by(|&&p, &&q| {
+                    length(sub(p, circle.center))
+                        .partial_cmp(&length(sub(q, circle.center)))
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                });
+                if let Some(axis) = closest.and_then(|&p| normalize(sub(circle.center, p))) {
+                    axes.push(axis);
+                }
+            }
+            (Convex::Polygon(_, a_normals), Convex::Polygon(_, b_normals)) => {
+                axes.extend_from_slice(a_normals);
+                axes.extend_from_slice(b_normals);
+            }
+        }
+
+        let project = |shape: &Convex, axis: [f32; 2]| match shape {
+            Convex::Circle(circle) => {
+                let c = dot(circle.center, axis);
+                (c - circle.radius, c + circle.radius)
+            }
+            Convex::Polygon(points, _) => {
+                points
+                    .iter()
+                    .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &p| {
+                        let d = dot(p, axis);
+                        (min.min(d), max.max(d))
+                    })
+            }
+        };
+
+        let mut best: Option<Contact> = None;
+        for axis in axes {
+            if axis == [0.0, 0.0] {
+                continue;
+            }
+            let (a_min, a_max) = project(&a, axis);
+            let (b_min, b_max) = project(&b, axis);
+            // how far the second shape has to go along the axis, or back along it, to get clear.
+            // Taking the shorter one rather than the overlap of the two ranges keeps the depth
+            // right when one shape is inside the other.
+            let forward = a_max - b_min;
+            let back = b_max - a_min;
+            if forward <= 0.0 || back <= 0.0 {
+                return None;
+            }
+            let (depth, normal) =
+                if forward < back || (forward == back && dot(between, axis) >= 0.0) {
+                    (forward, axis)
+                } else {
+                    (back, scale(axis, -1.0))
+                };
+            if best.is_none_or(|best| depth < best.depth) {
+                best = Some(Contact { normal, depth });
+            }
+        }
+        best
+    }
+
+    // Casts a ray from `origin` along `direction` (any length) up to `max_distance`. Rays that
+    // start inside the shape hit straight away, with the normal facing back along the ray.
+    pub fn raycast(
+        &self,
+        origin: [f32; 2],
+        direction: [f32; 2],
+        max_distance: f32,
+    ) -> Option<RayHit> {
+        if self.is_empty() {
+            return None;
+        }
+        let direction = normalize(direction)?;
+        if self.contains(origin) {
+            return Some(RayHit {
+                distance: 0.0,
+                point: origin,
+                normal: scale(direction, -1.0),
+            });
+        }
+
+        let (distance, normal) = match self.convex() {
+            Convex::Circle(circle) => {
+                let to_center = sub(circle.center, origin);
+                let along = dot(to_center, direction);
+                let off_sq = dot(to_center, to_center) - along * along;
+                let r_sq = circle.radius * circle.radius;
+                if off_sq > r_sq {
+                    return None;
+                }
+                let distance = along - (r_sq - off_sq).sqrt();
+                if distance < 0.0 {
+                    return None;
+                }
+                let point = add(origin, scale(direction, distance));
+                let normal = normalize(sub(point, circle.center)).unwrap_or([0.0, 0.0]);
+                (distance, normal)
+            }
+            Convex::Polygon(points, normals) => {
+                // clip the ray against each edge's half plane
+                let mut enter = 0.0;
+                let mut exit = max_distance;
+                let mut enter_normal = None;
+                for (&p, &n) in points.iter().zip(normals.iter()) {
+                    let towards = dot(n, direction);
+                    let outside = dot(n, sub(origin, p));
+                    if towards == 0.0 {
+                        if outside > 0.0 {
+                            return None;
+                        }
+                        continue;
+                    }
+                    let t = -outside / towards;
+                    if towards < 0.0 {
+                        if t > enter {
+                            enter = t;
+                            enter_normal = Some(n);
+                        }
+                    } else if t < exit {
+                        exit = t;
+                    }
+                    if enter > exit {
+                        return None;
+                    }
+                }
+                (enter, enter_normal?)
+            }
+        };
+
+        if distance > max_distance {
+            return None;
+        }
+        Some(RayHit {
+            distance,
+            point: add(origin, scale(direction, distance)),
+            normal,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    fn assert_contact(contact: Option<Contact>, normal: [f32; 2], depth: f32) {
+        let contact = contact.expect("shapes should overlap");
+        assert!(
+            close(contact.normal[0], normal[0]) && close(contact.normal[1], normal[1]),
+            "normal {:?}, expected {:?}",
+            contact.normal,
+            normal
+        );
+        assert!(close(contact.depth, depth), "depth {}", contact.depth);
+    }
+
+    // each kind of shape covering the unit square moved `x` along, or the circle inside it
+    fn unit_shapes(x: f32) -> Vec<Shape> {
+        vec![
+            Aabb::new([x, 0.0], [1.0, 1.0]).into(),
+            Circle::new([x + 0.5, 0.5], 0.5).into(),
+            OrientedRect::rotcenter([x, 0.0], [1.0, 1.0], 0.0).into(),
+            Polygon::new(vec![[x, 0.0], [x + 1.0, 0.0], [x + 1.0, 1.0], [x, 1.0]]).into(),
+        ]
+    }
+
+    #[test]
+    fn every_pair_overlaps() {
+        for a in unit_shapes(0.0) {
+            for b in unit_shapes(0.75) {
+                assert!(a.overlaps(&b), "{:?} {:?}", a, b);
+                assert_contact(a.contact(&b), [1.0, 0.0], 0.25);
+                assert_contact(b.contact(&a), [-1.0, 0.0], 0.25);
+            }
+        }
+    }
+
+    #[test]
+    fn touching_and_apart_pairs_dont_overlap() {
+        for a in unit_shapes(0.0) {
+            for x in &[1.0, 2.0] {
+                for b in unit_shapes(*x) {
+                    assert!(!a.overlaps(&b), "{:?} {:?}", a, b);
+                    assert_eq!(b.contact(&a), None);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn contained_shapes_push_out_the_short_way() {
+        let big = Shape::from(Aabb::new([0.0, 0.0], [4.0, 4.0]));
+        let small = Shape::from(Aabb::new([0.5, 1.5], [1.0, 1.0]));
+        assert_contact(big.contact(&small), [-1.0, 0.0], 1.5);
+        assert_contact(small.contact(&big), [1.0, 0.0], 1.5);
+
+        let circle = Shape::from(Circle::new([1.0, 2.0], 0.5));
+        assert_contact(big.contact(&circle), [-1.0, 0.0], 1.5);
+
+        let inner = Shape::from(Circle::new([1.0, 2.0], 0.5));
+        let outer = Shape::from(Circle::new([1.0, 2.5], 2.0));
+        assert_contact(outer.contact(&inner), [0.0, -1.0], 2.0);
+    }
+
+    #[test]
+    fn moving_by_the_contact_just_separates() {
+        let a = Shape::from(Aabb::new([0.0, 0.0], [4.0, 4.0]));
+        let b = Aabb::new([0.5, 1.5], [1.0, 1.0]);
+        let contact = a.contact(&b.into()).unwrap();
+        let moved = |depth: f32| -> Shape {
+            Aabb::new(add(b.min, scale(contact.normal, depth)), [1.0, 1.0]).into()
+        };
+        assert!(a.overlaps(&moved(contact.depth - 0.01)));
+        assert!(!a.overlaps(&moved(contact.depth)));
+    }
+
+    #[test]
+    fn concentric_circles() {
+        let a = Shape::from(Circle::new([1.0, 1.0], 1.0));
+        let b = Shape::from(Circle::new([1.0, 1.0], 0.5));
+        assert_contact(a.contact(&b), [1.0, 0.0], 1.5);
+    }
+
+    #[test]
+    fn circle_off_a_corner() {
+        let square = Shape::from(Aabb::new([0.0, 0.0], [1.0, 1.0]));
+        // overlaps on both edge normals, but not on the axis through the corner
+        assert!(!square.overlaps(&Circle::new([1.5, 1.5], 0.6).into()));
+
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        let depth = 0.8 - 0.5 * std::f32::consts::SQRT_2;
+        assert_contact(
+            square.contact(&Circle::new([1.5, 1.5], 0.8).into()),
+            [half, half],
+            depth,
+        );
+    }
+
+    #[test]
+    fn turned_rectangle() {
+        // a diamond with its points 1.414 from (1, 1)
+        let diamond = Shape::from(OrientedRect::rotcenter([0.0, 0.0], [2.0, 2.0], 45.0));
+        assert!(diamond.contains([1.0, 1.0]));
+        assert!(diamond.contains([2.4, 1.0]));
+        assert!(!diamond.contains([0.05, 0.05]));
+
+        let tip = Shape::from(Aabb::new([2.3, 0.5], [1.0, 1.0]));
+        assert_contact(
+            diamond.contact(&tip),
+            [1.0, 0.0],
+            std::f32::consts::SQRT_2 - 1.3,
+        );
+    }
+
+    #[test]
+    fn contains() {
+        for shape in unit_shapes(0.0) {
+            assert!(shape.contains([0.5, 0.5]), "{:?}", shape);
+            assert!(shape.contains([1.0, 0.5]), "{:?}", shape);
+            assert!(!shape.contains([1.1, 0.5]), "{:?}", shape);
+            assert!(!shape.contains([0.5, -0.1]), "{:?}", shape);
+        }
+        let circle = Shape::from(Circle::new([0.5, 0.5], 0.5));
+        assert!(!circle.contains([0.05, 0.05]));
+    }
+
+    #[test]
+    fn polygons_with_under_three_points_are_empty() {
+        let big = Shape::from(Aabb::new([-10.0, -10.0], [20.0, 20.0]));
+        for points in [vec![], vec![[0.0, 0.0]], vec![[0.0, 0.0], [1.0, 1.0]]] {
+            let polygon = Shape::from(Polygon::new(points));
+            assert!(!polygon.contains([0.0, 0.0]));
+            assert!(!polygon.overlaps(&big));
+            assert!(!big.overlaps(&polygon));
+            assert_eq!(polygon.raycast([-1.0, -1.0], [1.0, 1.0], 10.0), None);
+        }
+    }
+
+    #[test]
+    fn raycast_hits_the_near_side() {
+        for shape in unit_shapes(0.0) {
+            // the direction doesn't have to be unit length
+            let hit = shape.raycast([-1.0, 0.5], [2.0, 0.0], 10.0).unwrap();
+            assert!(close(hit.distance, 1.0), "{:?} {:?}", shape, hit);
+            assert!(close(hit.point[0], 0.0) && close(hit.point[1], 0.5));
+            assert!(close(hit.normal[0], -1.0) && close(hit.normal[1], 0.0));
+
+            assert_eq!(shape.raycast([-1.0, 0.5], [-1.0, 0.0], 10.0), None);
+            assert_eq!(shape.raycast([-1.0, 0.5], [0.0, 0.0], 10.0), None);
+        }
+    }
+
+    #[test]
+    fn raycast_parallel_to_an_edge() {
+        let square = Shape::from(Aabb::new([0.0, 0.0], [1.0, 1.0]));
+        // along the top edge counts, like `contains` on the edge does
+        let hit = square.raycast([-1.0, 0.0], [1.0, 0.0], 10.0).unwrap();
+        assert!(close(hit.distance, 1.0));
+        assert!(close(hit.normal[0], -1.0));
+        // just above it misses
+        assert_eq!(square.raycast([-1.0, -0.1], [1.0, 0.0], 10.0), None);
+        assert_eq!(square.raycast([0.5, -0.1], [1.0, 0.0], 10.0), None);
+    }
+
+    #[test]
+    fn raycast_starting_inside() {
+        for shape in unit_shapes(0.0) {
+            let hit = shape.raycast([0.5, 0.5], [0.0, 3.0], 10.0).unwrap();
+            assert_eq!(hit.distance, 0.0);
+            assert_eq!(hit.point, [0.5, 0.5]);
+            assert_eq!(hit.normal, [0.0, -1.0]);
+        }
+    }
+
+    #[test]
+    fn raycast_stops_at_max_distance() {
+        for shape in unit_shapes(0.0) {
+            assert_eq!(shape.raycast([-1.0, 0.5], [1.0, 0.0], 0.5), None);
+            assert!(shape.raycast([-1.0, 0.5], [1.0, 0.0], 1.0).is_some());
+        }
+    }
+}
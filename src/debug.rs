@@ -0,0 +1,136 @@
+use std::fmt;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+
+use vulkano::instance::debug::{DebugCallback, Message, MessageSeverity, MessageType};
+use vulkano::instance::{layers_list, Instance};
+
+pub const VALIDATION_LAYER: &str = "VK_LAYER_KHRONOS_validation";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Verbose,
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn of(severity: &MessageSeverity) -> Self {
+        if severity.error {
+            Severity::Error
+        } else if severity.warning {
+            Severity::Warning
+        } else if severity.information {
+            Severity::Info
+        } else {
+            Severity::Verbose
+        }
+    }
+}
+
+impl From<Severity> for log::Level {
+    fn from(severity: Severity) -> Self {
+        match severity {
+            Severity::Verbose => log::Level::Trace,
+            Severity::Info => log::Level::Info,
+            Severity::Warning => log::Level::Warn,
+            Severity::Error => log::Level::Error,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Severity::Verbose => "verbose",
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// gets the severity, the layer that sent the message and the message itself
+pub type DebugLogger = Arc<dyn Fn(Severity, &str, &str) + Send + Sync>;
+
+/// Validation layer settings, see `ContextBuilder::debug`.
+#[derive(Clone)]
+pub struct DebugOptions {
+    // messages less severe than this are never sent to the logger
+    pub min_severity: Severity,
+    pub logger: DebugLogger,
+}
+
+impl DebugOptions {
+    pub fn new() -> Self {
+        DebugOptions {
+            min_severity: Severity::Warning,
+            logger: Arc::new(
+                |severity, layer, message| log::log!(target: "vulkan", log::Level::from(severity), "{}: {}", layer, message),
+            ),
+        }
+    }
+}
+
+impl Default for DebugOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The validation layer comes with the Vulkan SDK rather than the driver, so it's only enabled if
+// it's actually installed.
+pub(crate) fn validation_layers() -> Vec<&'static str> {
+    let available = match layers_list() {
+        Ok(mut layers) => layers.any(|layer| layer.name() == VALIDATION_LAYER),
+        Err(_) => false,
+    };
+
+    if available {
+        vec![VALIDATION_LAYER]
+    } else {
+        log::warn!(
+            "{} isn't installed, validation messages won't be shown",
+            VALIDATION_LAYER
+        );
+        vec![]
+    }
+}
+
+pub(crate) fn create_callback(
+    instance: &Arc<Instance>,
+    options: &DebugOptions,
+) -> Option<DebugCallback> {
+    let min_severity = options.min_severity;
+    let severity = MessageSeverity {
+        error: true,
+        warning: min_severity <= Severity::Warning,
+        information: min_severity <= Severity::Info,
+        verbose: min_severity <= Severity::Verbose,
+    };
+
+    // the logger is only ever called, a panic in it can't leave it half updated
+    let logger = AssertUnwindSafe(options.logger.clone());
+    let callback = DebugCallback::new(
+        instance,
+        severity,
+        MessageType::all(),
+        move |msg: &Message| {
+            (logger.0)(
+                Severity::of(&msg.severity),
+                msg.layer_prefix,
+                msg.description,
+            )
+        },
+    );
+
+    match callback {
+        Ok(callback) => Some(callback),
+        Err(e) => {
+            log::warn!("Couldn't set up the debug callback: {}", e);
+            None
+        }
+    }
+}
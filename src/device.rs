@@ -0,0 +1,322 @@
+use std::env;
+use std::fmt;
+use std::sync::Arc;
+
+use vulkano::device::{Device, DeviceExtensions, Features, Queue};
+use vulkano::instance::{Instance, PhysicalDevice, PhysicalDeviceType, QueueFamily};
+use vulkano::swapchain::Surface;
+
+use winit::Window;
+
+use crate::error::{PumiceError, PumiceResult};
+
+// overrides whatever preference the game asked for, e.g. PUMICE_DEVICE=software to pin lavapipe in
+// CI or PUMICE_DEVICE=1 to pick the second device
+pub const DEVICE_ENV_VAR: &str = "PUMICE_DEVICE";
+
+#[derive(Clone, Debug, PartialEq, Default)]
+pub enum DevicePreference {
+    // discrete, then integrated, then virtual, then software
+    #[default]
+    Default,
+    Discrete,
+    Integrated,
+    Software,
+    // case insensitive substring of the device name
+    Name(String),
+    Index(usize),
+}
+
+impl DevicePreference {
+    pub fn from_env() -> Option<Self> {
+        env::var(DEVICE_ENV_VAR)
+            .ok()
+            .map(|value| Self::parse(&value))
+    }
+
+    pub fn parse(value: &str) -> Self {
+        let value = value.trim();
+        match value.to_lowercase().as_str() {
+            "" | "default" => DevicePreference::Default,
+            "discrete" => DevicePreference::Discrete,
+            "integrated" => DevicePreference::Integrated,
+            "software" | "cpu" => DevicePreference::Software,
+            _ => match value.parse::<usize>() {
+                Ok(index) => DevicePreference::Index(index),
+                Err(_) => DevicePreference::Name(value.to_string()),
+            },
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DeviceType {
+    Discrete,
+    Integrated,
+    Virtual,
+    Software,
+    Other,
+}
+
+impl From<PhysicalDeviceType> for DeviceType {
+    fn from(ty: PhysicalDeviceType) -> Self {
+        match ty {
+            PhysicalDeviceType::DiscreteGpu => DeviceType::Discrete,
+            PhysicalDeviceType::IntegratedGpu => DeviceType::Integrated,
+            PhysicalDeviceType::VirtualGpu => DeviceType::Virtual,
+            PhysicalDeviceType::Cpu => DeviceType::Software,
+            PhysicalDeviceType::Other => DeviceType::Other,
+        }
+    }
+}
+
+impl DeviceType {
+    fn rank(self) -> u8 {
+        match self {
+            DeviceType::Discrete => 0,
+            DeviceType::Integrated => 1,
+            DeviceType::Virtual => 2,
+            DeviceType::Software => 3,
+            DeviceType::Other => 4,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DeviceLimits {
+    pub max_image_dimension_2d: u32,
+    pub max_framebuffer_width: u32,
+    pub max_framebuffer_height: u32,
+    // bitmask of supported MSAA sample counts, bit n set means 2^n samples
+    pub framebuffer_color_sample_counts: u32,
+    pub max_memory_allocation_count: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct DeviceInfo {
+    pub index: usize,
+    pub name: String,
+    pub device_type: DeviceType,
+    pub api_version: String,
+    pub driver_version: u32,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    pub limits: DeviceLimits,
+}
+
+impl DeviceInfo {
+    pub fn new(physical: PhysicalDevice) -> Self {
+        let api_version = physical.api_version();
+        let limits = physical.limits();
+
+        DeviceInfo {
+            index: physical.index(),
+            name: physical.name(),
+            device_type: physical.ty().into(),
+            api_version: format!(
+                "{}.{}.{}",
+                api_version.major, api_version.minor, api_version.patch
+            ),
+            driver_version: physical.driver_version(),
+            vendor_id: physical.pci_vendor_id(),
+            device_id: physical.pci_device_id(),
+            limits: DeviceLimits {
+                max_image_dimension_2d: limits.max_image_dimension_2d(),
+                max_framebuffer_width: limits.max_framebuffer_width(),
+                max_framebuffer_height: limits.max_framebuffer_height(),
+                framebuffer_color_sample_counts: limits.framebuffer_color_sample_counts(),
+                max_memory_allocation_count: limits.max_memory_allocation_count(),
+            },
+        }
+    }
+}
+
+impl fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[{}] {} ({:?}), Vulkan {}, driver {:#x}, max 2D image {}",
+            self.index,
+            self.name,
+            self.device_type,
+            self.api_version,
+            self.driver_version,
+            self.limits.max_image_dimension_2d,
+        )
+    }
+}
+
+// Rounds down to a power of two and then to the highest count the device supports. 1 sample is
+// always supported.
+pub(crate) fn clamp_samples(requested: u32, limits: &DeviceLimits) -> u32 {
+    // Vulkan doesn't go past 64 samples, and next_power_of_two overflows above 2^31
+    let requested = requested.clamp(1, 64);
+    let mut samples = requested.next_power_of_two();
+    if samples > requested {
+        samples /= 2;
+    }
+
+    while samples > 1 && limits.framebuffer_color_sample_counts & samples == 0 {
+        samples /= 2;
+    }
+    samples
+}
+
+// a device is only usable if one of its queue families can draw to the window
+pub(crate) fn graphics_queue_family<'a>(
+    physical: PhysicalDevice<'a>,
+    surface: &Arc<Surface<Window>>,
+) -> Option<QueueFamily<'a>> {
+    physical
+        .queue_families()
+        .find(|&q| q.supports_graphics() && surface.is_supported(q).unwrap_or(false))
+}
+
+pub(crate) fn select_physical_device<'a>(
+    instance: &'a Arc<Instance>,
+    surface: &Arc<Surface<Window>>,
+    preference: &DevicePreference,
+) -> Option<(PhysicalDevice<'a>, QueueFamily<'a>)> {
+    let mut candidates = PhysicalDevice::enumerate(instance)
+        .filter_map(|physical| {
+            graphics_queue_family(physical, surface).map(|queue_family| (physical, queue_family))
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by_key(|(physical, _)| DeviceType::from(physical.ty()).rank());
+
+    let preferred = match preference {
+        DevicePreference::Default => None,
+        DevicePreference::Discrete => candidates
+            .iter()
+            .find(|(physical, _)| DeviceType::from(physical.ty()) == DeviceType::Discrete),
+        DevicePreference::Integrated => candidates
+            .iter()
+            .find(|(physical, _)| DeviceType::from(physical.ty()) == DeviceType::Integrated),
+        DevicePreference::Software => candidates
+            .iter()
+            .find(|(physical, _)| DeviceType::from(physical.ty()) == DeviceType::Software),
+        DevicePreference::Name(name) => candidates.iter().find(|(physical, _)| {
+            physical
+                .name()
+                .to_lowercase()
+                .contains(&name.to_lowercase())
+        }),
+        DevicePreference::Index(index) => candidates
+            .iter()
+            .find(|(physical, _)| physical.index() == *index),
+    };
+
+    if preferred.is_none() && *preference != DevicePreference::Default {
+        log::warn!(
+            "No usable device matches {:?}, falling back to the default",
+            preference
+        );
+    }
+
+    preferred.or_else(|| candidates.first()).cloned()
+}
+
+pub(crate) fn create_device(
+    physical: PhysicalDevice,
+    queue_family: QueueFamily,
+) -> PumiceResult<(Arc<Device>, Arc<Queue>)> {
+    let (device, mut queues) = Device::new(
+        physical,
+        &Features::none(),
+        &DeviceExtensions {
+            khr_storage_buffer_storage_class: true,
+            khr_swapchain: true,
+            ..DeviceExtensions::none()
+        },
+        [(queue_family, 0.5)].iter().cloned(),
+    )?;
+
+    let queue = queues.next().ok_or(PumiceError::NoSuitableDevice)?;
+    Ok((device, queue))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_sample_counts(sample_counts: u32) -> DeviceLimits {
+        DeviceLimits {
+            max_image_dimension_2d: 4096,
+            max_framebuffer_width: 4096,
+            max_framebuffer_height: 4096,
+            framebuffer_color_sample_counts: sample_counts,
+            max_memory_allocation_count: 4096,
+        }
+    }
+
+    #[test]
+    fn parse_device_preferences() {
+        assert_eq!(DevicePreference::parse(""), DevicePreference::Default);
+        assert_eq!(
+            DevicePreference::parse("default"),
+            DevicePreference::Default
+        );
+        assert_eq!(
+            DevicePreference::parse(" Discrete "),
+            DevicePreference::Discrete
+        );
+        assert_eq!(
+            DevicePreference::parse("INTEGRATED"),
+            DevicePreference::Integrated
+        );
+        assert_eq!(
+            DevicePreference::parse("software"),
+            DevicePreference::Software
+        );
+        assert_eq!(DevicePreference::parse("cpu"), DevicePreference::Software);
+    }
+
+    #[test]
+    fn parse_index_and_name() {
+        assert_eq!(DevicePreference::parse("0"), DevicePreference::Index(0));
+        assert_eq!(DevicePreference::parse(" 12"), DevicePreference::Index(12));
+        // names keep their case, they're compared case insensitively when picking the device
+        assert_eq!(
+            DevicePreference::parse("llvmpipe"),
+            DevicePreference::Name("llvmpipe".to_string())
+        );
+        assert_eq!(
+            DevicePreference::parse(" GeForce GTX "),
+            DevicePreference::Name("GeForce GTX".to_string())
+        );
+        assert_eq!(
+            DevicePreference::parse("-1"),
+            DevicePreference::Name("-1".to_string())
+        );
+    }
+
+    #[test]
+    fn samples_round_down_to_a_power_of_two() {
+        let limits = with_sample_counts(0b111_1111);
+        assert_eq!(clamp_samples(0, &limits), 1);
+        assert_eq!(clamp_samples(1, &limits), 1);
+        assert_eq!(clamp_samples(3, &limits), 2);
+        assert_eq!(clamp_samples(4, &limits), 4);
+        assert_eq!(clamp_samples(7, &limits), 4);
+        assert_eq!(clamp_samples(63, &limits), 32);
+        assert_eq!(clamp_samples(64, &limits), 64);
+    }
+
+    #[test]
+    fn samples_are_limited_by_the_device() {
+        // 1, 2, 4 and 8 samples
+        let limits = with_sample_counts(0b1111);
+        assert_eq!(clamp_samples(16, &limits), 8);
+        assert_eq!(clamp_samples(100, &limits), 8);
+        assert_eq!(clamp_samples(u32::MAX, &limits), 8);
+        assert_eq!(clamp_samples((1 << 31) + 1, &limits), 8);
+
+        // 1 and 4 samples, so 2 goes down to 1
+        let limits = with_sample_counts(0b101);
+        assert_eq!(clamp_samples(2, &limits), 1);
+        assert_eq!(clamp_samples(8, &limits), 4);
+
+        // 1 sample is always allowed
+        assert_eq!(clamp_samples(8, &with_sample_counts(0)), 1);
+    }
+}
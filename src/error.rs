@@ -1,27 +1,90 @@
 use std;
 use std::error::Error;
 use std::fmt;
+use std::sync::Arc;
 
 use lyon;
 
+use vulkano::device::DeviceCreationError;
+use vulkano::instance::InstanceCreationError;
+use vulkano::memory::DeviceMemoryAllocError;
+use vulkano::swapchain::{AcquireError, CapabilitiesError, SwapchainCreationError};
+use vulkano::sync::FlushError;
+use vulkano::OomError;
+
+// the underlying error, kept around for `source()`
+pub type ErrorSource = Arc<dyn Error + Send + Sync>;
+
 #[derive(Debug, Clone)]
 pub enum PumiceError {
     LyonError(String),
+    GamepadError(String),
+    ReplayError(String),
+    InstanceCreation(ErrorSource),
+    NoSuitableDevice,
+    DeviceCreation(ErrorSource),
+    SurfaceCreation(ErrorSource),
+    SwapchainCreation(ErrorSource),
+    Pipeline(ErrorSource),
+    OutOfMemory(ErrorSource),
+    DeviceLost,
+    SurfaceLost,
+    // anything else that goes wrong recording or submitting a frame
+    Render(ErrorSource),
+    Asset(String),
 }
 
 pub type PumiceResult<T> = Result<T, PumiceError>;
 
+impl PumiceError {
+    pub fn pipeline(err: impl Error + Send + Sync + 'static) -> Self {
+        PumiceError::Pipeline(Arc::new(err))
+    }
+
+    pub fn render(err: impl Error + Send + Sync + 'static) -> Self {
+        PumiceError::Render(Arc::new(err))
+    }
+}
+
 impl fmt::Display for PumiceError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             PumiceError::LyonError(ref s) => write!(f, "Lyon error: {}", s),
+            PumiceError::GamepadError(ref s) => write!(f, "Gamepad error: {}", s),
+            PumiceError::ReplayError(ref s) => write!(f, "Replay error: {}", s),
+            PumiceError::InstanceCreation(ref e) => {
+                write!(f, "Couldn't create a Vulkan instance: {}", e)
+            }
+            PumiceError::NoSuitableDevice => {
+                write!(f, "No device with Vulkan support can draw to this window")
+            }
+            PumiceError::DeviceCreation(ref e) => write!(f, "Couldn't create the device: {}", e),
+            PumiceError::SurfaceCreation(ref e) => write!(f, "Couldn't create the window: {}", e),
+            PumiceError::SwapchainCreation(ref e) => {
+                write!(f, "Couldn't create the swapchain: {}", e)
+            }
+            PumiceError::Pipeline(ref e) => write!(f, "Couldn't build the pipeline: {}", e),
+            PumiceError::OutOfMemory(ref e) => write!(f, "Out of memory: {}", e),
+            PumiceError::DeviceLost => write!(f, "The device was lost"),
+            PumiceError::SurfaceLost => write!(f, "The window surface was lost"),
+            PumiceError::Render(ref e) => write!(f, "Render error: {}", e),
+            PumiceError::Asset(ref s) => write!(f, "Asset error: {}", s),
         }
     }
 }
 
 impl Error for PumiceError {
-    fn cause(&self) -> Option<&dyn Error> {
-        None
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            PumiceError::InstanceCreation(ref e)
+            | PumiceError::DeviceCreation(ref e)
+            | PumiceError::SurfaceCreation(ref e)
+            | PumiceError::SwapchainCreation(ref e)
+            | PumiceError::Pipeline(ref e)
+            | PumiceError::OutOfMemory(ref e)
+            | PumiceError::Render(ref e) => Some(e.as_ref()),
+            _ => None,
+        }
     }
 }
 
@@ -31,3 +94,103 @@ impl From<lyon::tessellation::TessellationError> for PumiceError {
         PumiceError::LyonError(fmtd)
     }
 }
+
+impl From<InstanceCreationError> for PumiceError {
+    fn from(err: InstanceCreationError) -> PumiceError {
+        PumiceError::InstanceCreation(Arc::new(err))
+    }
+}
+
+impl From<DeviceCreationError> for PumiceError {
+    fn from(err: DeviceCreationError) -> PumiceError {
+        PumiceError::DeviceCreation(Arc::new(err))
+    }
+}
+
+impl From<vulkano_win::CreationError> for PumiceError {
+    fn from(err: vulkano_win::CreationError) -> PumiceError {
+        PumiceError::SurfaceCreation(Arc::new(err))
+    }
+}
+
+impl From<SwapchainCreationError> for PumiceError {
+    fn from(err: SwapchainCreationError) -> PumiceError {
+        match err {
+            SwapchainCreationError::OomError(e) => e.into(),
+            SwapchainCreationError::DeviceLost => PumiceError::DeviceLost,
+            SwapchainCreationError::SurfaceLost => PumiceError::SurfaceLost,
+            err => PumiceError::SwapchainCreation(Arc::new(err)),
+        }
+    }
+}
+
+impl From<OomError> for PumiceError {
+    fn from(err: OomError) -> PumiceError {
+        PumiceError::OutOfMemory(Arc::new(err))
+    }
+}
+
+impl From<DeviceMemoryAllocError> for PumiceError {
+    fn from(err: DeviceMemoryAllocError) -> PumiceError {
+        PumiceError::OutOfMemory(Arc::new(err))
+    }
+}
+
+impl From<CapabilitiesError> for PumiceError {
+    fn from(err: CapabilitiesError) -> PumiceError {
+        match err {
+            CapabilitiesError::OomError(e) => e.into(),
+            CapabilitiesError::SurfaceLost => PumiceError::SurfaceLost,
+        }
+    }
+}
+
+impl From<AcquireError> for PumiceError {
+    fn from(err: AcquireError) -> PumiceError {
+        match err {
+            AcquireError::OomError(e) => e.into(),
+            AcquireError::DeviceLost => PumiceError::DeviceLost,
+            AcquireError::SurfaceLost => PumiceError::SurfaceLost,
+            err => PumiceError::render(err),
+        }
+    }
+}
+
+impl From<FlushError> for PumiceError {
+    fn from(err: FlushError) -> PumiceError {
+        match err {
+            FlushError::OomError(e) => e.into(),
+            FlushError::DeviceLost => PumiceError::DeviceLost,
+            FlushError::SurfaceLost => PumiceError::SurfaceLost,
+            err => PumiceError::render(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lost_swapchains_can_be_recovered() {
+        // run recreates the device or surface for these, so they can't end up as SwapchainCreation
+        match PumiceError::from(SwapchainCreationError::DeviceLost) {
+            PumiceError::DeviceLost => {}
+            err => panic!("{:?}", err),
+        }
+        match PumiceError::from(SwapchainCreationError::SurfaceLost) {
+            PumiceError::SurfaceLost => {}
+            err => panic!("{:?}", err),
+        }
+        match PumiceError::from(SwapchainCreationError::OomError(
+            OomError::OutOfDeviceMemory,
+        )) {
+            PumiceError::OutOfMemory(_) => {}
+            err => panic!("{:?}", err),
+        }
+        match PumiceError::from(SwapchainCreationError::SurfaceInUse) {
+            PumiceError::SwapchainCreation(e) => assert!(e.to_string().contains("surface")),
+            err => panic!("{:?}", err),
+        }
+    }
+}
@@ -0,0 +1,9 @@
+use crate::gamepad::GamepadEvent;
+
+// Everything `run` passes to `handle_event`. Window and keyboard events come straight from winit,
+// gamepads are polled separately since winit doesn't know about them.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    Winit(winit::Event),
+    Gamepad(GamepadEvent),
+}
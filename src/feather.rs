@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+
+use lyon::tessellation::VertexBuffers;
+
+use crate::error::{PumiceError, PumiceResult};
+use crate::Vertex;
+
+// corners sharper than this get clipped instead of mitered all the way out
+const MAX_MITER: f32 = 2.0;
+
+// an edge's two vertices and the third vertex of its triangle
+type Edge = (u16, u16, u16);
+
+fn normalize(v: [f32; 2]) -> [f32; 2] {
+    let len = (v[0] * v[0] + v[1] * v[1]).sqrt();
+    if len > 0.0 {
+        [v[0] / len, v[1] / len]
+    } else {
+        [0.0, 0.0]
+    }
+}
+
+// Adds a `width` wide strip that fades from the shape's color to transparent around the outline of
+// everything in `geometry` from `first_index` on. The outline is pulled in by half the width so the
+// shape stays the same size. This is a cheap stand-in for MSAA, mostly for software renderers.
+pub(crate) fn add_fringe(
+    geometry: &mut VertexBuffers<Vertex, u16>,
+    first_index: usize,
+    width: f32,
+) -> PumiceResult<()> {
+    // edges that only belong to one triangle are on the outline, kept with the triangle's third
+    // vertex to tell which side is outside
+    let mut edges: HashMap<(u16, u16), Option<Edge>> = HashMap::new();
+    for triangle in geometry.indices[first_index..].chunks(3) {
+        if let [a, b, c] = *triangle {
+            for &(from, to, opposite) in &[(a, b, c), (b, c, a), (c, a, b)] {
+                let key = (from.min(to), from.max(to));
+                edges
+                    .entry(key)
+                    .and_modify(|edge| *edge = None)
+                    .or_insert(Some((from, to, opposite)));
+            }
+        }
+    }
+    let outline = edges.values().filter_map(|&edge| edge).collect::<Vec<_>>();
+
+    let mut edge_normals: HashMap<u16, Vec<[f32; 2]>> = HashMap::new();
+    for &(from, to, opposite) in &outline {
+        let a = geometry.vertices[from as usize].position;
+        let b = geometry.vertices[to as usize].position;
+        let c = geometry.vertices[opposite as usize].position;
+
+        let mut normal = normalize([b[1] - a[1], a[0] - b[0]]);
+        let towards_opposite = (c[0] - a[0]) * normal[0] + (c[1] - a[1]) * normal[1];
+        if towards_opposite > 0.0 {
+            normal = [-normal[0], -normal[1]];
+        }
+
+        edge_normals.entry(from).or_default().push(normal);
+        edge_normals.entry(to).or_default().push(normal);
+    }
+
+    // every outline vertex gets an outer copy, checked up front so a shape that doesn't fit is left
+    // untouched
+    if geometry.vertices.len() + edge_normals.len() > u16::MAX as usize {
+        return Err(PumiceError::LyonError(
+            "Too many vertices for 16 bit indices".to_string(),
+        ));
+    }
+
+    let mut outer_vertices = HashMap::new();
+    for (&index, normals) in edge_normals.iter() {
+        let sum = normals
+            .iter()
+            .fold([0.0, 0.0], |acc, n| [acc[0] + n[0], acc[1] + n[1]]);
+        let normal = normalize(sum);
+        let miter = (normal[0] * normals[0][0] + normal[1] * normals[0][1]).max(1.0 / MAX_MITER);
+        let offset = [
+            normal[0] * width / 2.0 / miter,
+            normal[1] * width / 2.0 / miter,
+        ];
+
+        let inner = &mut geometry.vertices[index as usize];
+        inner.position = [inner.position[0] - offset[0], inner.position[1] - offset[1]];
+
+        let mut outer = *inner;
+        outer.position = [
+            inner.position[0] + offset[0] * 2.0,
+            inner.position[1] + offset[1] * 2.0,
+        ];
+        outer.color[3] = 0.0;
+        outer.gradient_color[3] = 0.0;
+
+        outer_vertices.insert(index, geometry.vertices.len() as u16);
+        geometry.vertices.push(outer);
+    }
+
+    for &(from, to, _) in &outline {
+        let outer_from = outer_vertices[&from];
+        let outer_to = outer_vertices[&to];
+        geometry
+            .indices
+            .extend_from_slice(&[from, to, outer_to, from, outer_to, outer_from]);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Paint;
+
+    fn geometry(positions: &[[f32; 2]], indices: &[u16]) -> VertexBuffers<Vertex, u16> {
+        let paint = Paint::from([1.0, 0.0, 0.0, 1.0]);
+        let mut geometry = VertexBuffers::new();
+        geometry.vertices = positions
+            .iter()
+            .map(|&position| paint.vertex(position, [0.0, 0.0, 0.0]))
+            .collect();
+        geometry.indices = indices.to_vec();
+        geometry
+    }
+
+    fn check_fringe(geometry: &VertexBuffers<Vertex, u16>, corners: usize) {
+        // an outer copy of each corner, and two triangles along each side
+        assert_eq!(geometry.vertices.len(), corners * 2);
+        assert_eq!(geometry.indices.len(), (corners - 2) * 3 + corners * 6);
+        for (i, vertex) in geometry.vertices.iter().enumerate() {
+            let alpha = if i < corners { 1.0 } else { 0.0 };
+            assert_eq!(vertex.color[3], alpha);
+            assert_eq!(vertex.gradient_color[3], alpha);
+        }
+    }
+
+    #[test]
+    fn quad_fringe() {
+        let mut quad = geometry(
+            &[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]],
+            &[0, 1, 2, 0, 2, 3],
+        );
+        add_fringe(&mut quad, 0, 0.1).unwrap();
+        check_fringe(&quad, 4);
+
+        // the corners move in and their outer copies out, both along the diagonal
+        let close = |a: [f32; 2], b: [f32; 2]| (a[0] - b[0]).abs() + (a[1] - b[1]).abs() < 1e-5;
+        assert!(close(quad.vertices[0].position, [0.05, 0.05]));
+        assert!(close(quad.vertices[2].position, [0.95, 0.95]));
+        let outer = quad.vertices[4..]
+            .iter()
+            .map(|vertex| vertex.position)
+            .collect::<Vec<_>>();
+        assert!(outer.iter().any(|&p| close(p, [-0.05, -0.05])));
+        assert!(outer.iter().any(|&p| close(p, [1.05, 1.05])));
+    }
+
+    #[test]
+    fn triangle_fringe() {
+        let mut triangle = geometry(&[[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]], &[0, 1, 2]);
+        add_fringe(&mut triangle, 0, 0.1).unwrap();
+        check_fringe(&triangle, 3);
+    }
+
+    #[test]
+    fn only_new_shapes_get_a_fringe() {
+        let mut geometry = geometry(
+            &[
+                [0.0, 0.0],
+                [1.0, 0.0],
+                [0.0, 1.0],
+                [2.0, 0.0],
+                [3.0, 0.0],
+                [2.0, 1.0],
+            ],
+            &[0, 1, 2, 3, 4, 5],
+        );
+        add_fringe(&mut geometry, 3, 0.1).unwrap();
+        assert_eq!(geometry.vertices.len(), 9);
+        assert_eq!(geometry.vertices[0].position, [0.0, 0.0]);
+    }
+
+    #[test]
+    fn too_many_vertices() {
+        let mut positions = vec![[0.0, 0.0]; u16::MAX as usize - 1];
+        positions[1] = [1.0, 0.0];
+        positions[2] = [0.0, 1.0];
+        let mut geometry = geometry(&positions, &[0, 1, 2]);
+
+        match add_fringe(&mut geometry, 0, 0.1) {
+            Err(PumiceError::LyonError(_)) => {}
+            result => panic!("{:?}", result),
+        }
+        // left as it was
+        assert_eq!(geometry.vertices.len(), u16::MAX as usize - 1);
+        assert_eq!(geometry.indices, vec![0, 1, 2]);
+        assert_eq!(geometry.vertices[0].position, [0.0, 0.0]);
+    }
+}
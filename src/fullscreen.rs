@@ -0,0 +1,78 @@
+use winit::dpi::{LogicalPosition, LogicalSize};
+use winit::{MonitorId, Window};
+
+/// How the window covers the screen. `None` means whichever monitor the window is currently on.
+///
+/// There's no exclusive fullscreen or video mode selection: winit 0.19 has no API for either, so
+/// the monitor always stays at its desktop resolution. Render at a lower resolution by shrinking
+/// the window instead.
+#[derive(Clone, Debug)]
+pub enum FullscreenMode {
+    Windowed,
+    // winit's fullscreen, which is borderless on the monitor on most platforms and its own space
+    // on macOS
+    Fullscreen(Option<MonitorId>),
+    // a decoration-less window moved and sized to cover the monitor
+    Borderless(Option<MonitorId>),
+}
+
+impl FullscreenMode {
+    pub fn is_windowed(&self) -> bool {
+        matches!(self, FullscreenMode::Windowed)
+    }
+}
+
+// the window's size and position before it left windowed mode, so it can be put back
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct WindowedGeometry {
+    position: Option<LogicalPosition>,
+    size: Option<LogicalSize>,
+}
+
+impl WindowedGeometry {
+    pub(crate) fn save(window: &Window) -> Self {
+        WindowedGeometry {
+            position: window.get_position(),
+            size: window.get_inner_size(),
+        }
+    }
+
+    fn restore(&self, window: &Window) {
+        if let Some(size) = self.size {
+            window.set_inner_size(size);
+        }
+        if let Some(position) = self.position {
+            window.set_position(position);
+        }
+    }
+}
+
+pub(crate) fn apply(window: &Window, mode: &FullscreenMode, windowed: Option<WindowedGeometry>) {
+    match mode {
+        FullscreenMode::Windowed => {
+            window.set_fullscreen(None);
+            window.set_decorations(true);
+            if let Some(geometry) = windowed {
+                geometry.restore(window);
+            }
+        }
+        FullscreenMode::Fullscreen(monitor) => {
+            window.set_decorations(true);
+            let monitor = monitor
+                .clone()
+                .unwrap_or_else(|| window.get_current_monitor());
+            window.set_fullscreen(Some(monitor));
+        }
+        FullscreenMode::Borderless(monitor) => {
+            let monitor = monitor
+                .clone()
+                .unwrap_or_else(|| window.get_current_monitor());
+            let hidpi_factor = monitor.get_hidpi_factor();
+
+            window.set_fullscreen(None);
+            window.set_decorations(false);
+            window.set_position(monitor.get_position().to_logical(hidpi_factor));
+            window.set_inner_size(monitor.get_dimensions().to_logical(hidpi_factor));
+        }
+    }
+}
@@ -0,0 +1,548 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "gilrs")]
+use crate::error::{PumiceError, PumiceResult};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct GamepadId(pub usize);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Button {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    Select,
+    Start,
+    Mode,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+// sticks range from -1.0 to 1.0, triggers from 0.0 to 1.0
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Axis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+impl Axis {
+    fn stick_pair(self) -> Option<(Axis, Axis)> {
+        match self {
+            Axis::LeftStickX | Axis::LeftStickY => Some((Axis::LeftStickX, Axis::LeftStickY)),
+            Axis::RightStickX | Axis::RightStickY => Some((Axis::RightStickX, Axis::RightStickY)),
+            Axis::LeftTrigger | Axis::RightTrigger => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum GamepadEvent {
+    Connected(GamepadId),
+    Disconnected(GamepadId),
+    ButtonPressed(GamepadId, Button),
+    ButtonReleased(GamepadId, Button),
+    AxisMoved(GamepadId, Axis, f32),
+}
+
+/// A source of raw gamepad events. Axis values coming out of a backend are unfiltered,
+/// deadzones are applied by `Gamepads`.
+pub trait GamepadBackend {
+    fn poll(&mut self) -> Option<GamepadEvent>;
+}
+
+/// A backend with no physical device behind it. Clones share the same queue, so a test can keep
+/// one handle and give the other to `Gamepads::set_backend`.
+#[derive(Clone, Default)]
+pub struct VirtualGamepad {
+    queue: Arc<Mutex<VecDeque<GamepadEvent>>>,
+}
+
+impl VirtualGamepad {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&self, event: GamepadEvent) {
+        self.queue.lock().unwrap().push_back(event);
+    }
+
+    pub fn connect(&self, id: GamepadId) {
+        self.push(GamepadEvent::Connected(id));
+    }
+
+    pub fn disconnect(&self, id: GamepadId) {
+        self.push(GamepadEvent::Disconnected(id));
+    }
+
+    pub fn press(&self, id: GamepadId, button: Button) {
+        self.push(GamepadEvent::ButtonPressed(id, button));
+    }
+
+    pub fn release(&self, id: GamepadId, button: Button) {
+        self.push(GamepadEvent::ButtonReleased(id, button));
+    }
+
+    pub fn set_axis(&self, id: GamepadId, axis: Axis, value: f32) {
+        self.push(GamepadEvent::AxisMoved(id, axis, value));
+    }
+}
+
+impl GamepadBackend for VirtualGamepad {
+    fn poll(&mut self) -> Option<GamepadEvent> {
+        self.queue.lock().unwrap().pop_front()
+    }
+}
+
+#[cfg(feature = "gilrs")]
+pub struct GilrsBackend {
+    gilrs: gilrs::Gilrs,
+}
+
+#[cfg(feature = "gilrs")]
+impl GilrsBackend {
+    pub fn new() -> PumiceResult<Self> {
+        match gilrs::Gilrs::new() {
+            Ok(gilrs) => Ok(GilrsBackend { gilrs }),
+            Err(e) => Err(PumiceError::GamepadError(format!("{}", e))),
+        }
+    }
+
+    fn map_button(button: gilrs::Button) -> Option<Button> {
+        match button {
+            gilrs::Button::South => Some(Button::South),
+            gilrs::Button::East => Some(Button::East),
+            gilrs::Button::North => Some(Button::North),
+            gilrs::Button::West => Some(Button::West),
+            gilrs::Button::LeftTrigger => Some(Button::LeftBumper),
+            gilrs::Button::RightTrigger => Some(Button::RightBumper),
+            gilrs::Button::Select => Some(Button::Select),
+            gilrs::Button::Start => Some(Button::Start),
+            gilrs::Button::Mode => Some(Button::Mode),
+            gilrs::Button::LeftThumb => Some(Button::LeftStick),
+            gilrs::Button::RightThumb => Some(Button::RightStick),
+            gilrs::Button::DPadUp => Some(Button::DPadUp),
+            gilrs::Button::DPadDown => Some(Button::DPadDown),
+            gilrs::Button::DPadLeft => Some(Button::DPadLeft),
+            gilrs::Button::DPadRight => Some(Button::DPadRight),
+            _ => None,
+        }
+    }
+
+    fn map_axis(axis: gilrs::Axis) -> Option<Axis> {
+        match axis {
+            gilrs::Axis::LeftStickX => Some(Axis::LeftStickX),
+            gilrs::Axis::LeftStickY => Some(Axis::LeftStickY),
+            gilrs::Axis::RightStickX => Some(Axis::RightStickX),
+            gilrs::Axis::RightStickY => Some(Axis::RightStickY),
+            _ => None,
+        }
+    }
+
+    // analog triggers are reported by gilrs as buttons with a value
+    fn map_trigger(button: gilrs::Button) -> Option<Axis> {
+        match button {
+            gilrs::Button::LeftTrigger2 => Some(Axis::LeftTrigger),
+            gilrs::Button::RightTrigger2 => Some(Axis::RightTrigger),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "gilrs")]
+impl GamepadBackend for GilrsBackend {
+    fn poll(&mut self) -> Option<GamepadEvent> {
+        use gilrs::EventType;
+
+        while let Some(gilrs::Event { id, event, .. }) = self.gilrs.next_event() {
+            let id = GamepadId(id.into());
+            let mapped = match event {
+                EventType::Connected => Some(GamepadEvent::Connected(id)),
+                EventType::Disconnected => Some(GamepadEvent::Disconnected(id)),
+                EventType::ButtonPressed(button, _) => {
+                    Self::map_button(button).map(|b| GamepadEvent::ButtonPressed(id, b))
+                }
+                EventType::ButtonReleased(button, _) => {
+                    Self::map_button(button).map(|b| GamepadEvent::ButtonReleased(id, b))
+                }
+                EventType::ButtonChanged(button, value, _) => {
+                    Self::map_trigger(button).map(|a| GamepadEvent::AxisMoved(id, a, value))
+                }
+                EventType::AxisChanged(axis, value, _) => {
+                    Self::map_axis(axis).map(|a| GamepadEvent::AxisMoved(id, a, value))
+                }
+                _ => None,
+            };
+
+            if mapped.is_some() {
+                return mapped;
+            }
+        }
+
+        None
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct GamepadState {
+    buttons: HashSet<Button>,
+    raw_axes: HashMap<Axis, f32>,
+    axes: HashMap<Axis, f32>,
+}
+
+impl GamepadState {
+    pub fn is_pressed(&self, button: Button) -> bool {
+        self.buttons.contains(&button)
+    }
+
+    pub fn axis(&self, axis: Axis) -> f32 {
+        self.axes.get(&axis).cloned().unwrap_or(0.0)
+    }
+
+    fn raw_axis(&self, axis: Axis) -> f32 {
+        self.raw_axes.get(&axis).cloned().unwrap_or(0.0)
+    }
+}
+
+/// Polls a `GamepadBackend`, tracks the state of each connected pad and applies deadzones.
+/// Sticks use a radial deadzone and both sticks and triggers are rescaled so the output
+/// still covers the full range once outside the deadzone.
+pub struct Gamepads {
+    backend: Option<Box<dyn GamepadBackend>>,
+    pads: HashMap<GamepadId, GamepadState>,
+    pub stick_deadzone: f32,
+    pub trigger_deadzone: f32,
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        #[cfg(feature = "gilrs")]
+        let backend = match GilrsBackend::new() {
+            Ok(backend) => Some(Box::new(backend) as Box<dyn GamepadBackend>),
+            Err(e) => {
+                log::warn!("Gamepad support disabled: {}", e);
+                None
+            }
+        };
+        #[cfg(not(feature = "gilrs"))]
+        let backend = None;
+
+        Gamepads {
+            backend,
+            pads: HashMap::new(),
+            stick_deadzone: 0.15,
+            trigger_deadzone: 0.05,
+        }
+    }
+
+    pub fn set_backend(&mut self, backend: impl GamepadBackend + 'static) {
+        self.backend = Some(Box::new(backend));
+        self.pads.clear();
+    }
+
+    pub fn connected(&self) -> impl Iterator<Item = GamepadId> + '_ {
+        self.pads.keys().cloned()
+    }
+
+    pub fn state(&self, id: GamepadId) -> Option<&GamepadState> {
+        self.pads.get(&id)
+    }
+
+    pub fn is_pressed(&self, id: GamepadId, button: Button) -> bool {
+        self.state(id).is_some_and(|pad| pad.is_pressed(button))
+    }
+
+    pub fn axis(&self, id: GamepadId, axis: Axis) -> f32 {
+        self.state(id).map_or(0.0, |pad| pad.axis(axis))
+    }
+
+    /// Drains the backend and returns the filtered events. Axis events are only emitted when the
+    /// value after the deadzone changes, so a resting stick doesn't produce a stream of zeros.
+    pub fn poll_events(&mut self) -> Vec<GamepadEvent> {
+        let mut events = Vec::new();
+        let mut backend = match self.backend.take() {
+            Some(backend) => backend,
+            None => return events,
+        };
+
+        while let Some(event) = backend.poll() {
+            self.process(event, &mut events);
+        }
+
+        self.backend = Some(backend);
+        events
+    }
+
+    // empties the backend without touching any pad's state, for while a replay stands in for
+    // live input
+    pub(crate) fn discard_events(&mut self) {
+        if let Some(backend) = &mut self.backend {
+            while backend.poll().is_some() {}
+        }
+    }
+
+    // Applies an event `poll_events` returned earlier, e.g. one read back from a recording. It's
+    // already past the deadzones, so it's stored as is.
+    pub(crate) fn apply(&mut self, event: GamepadEvent) {
+        match event {
+            GamepadEvent::Connected(id) => {
+                self.pads.insert(id, GamepadState::default());
+            }
+            GamepadEvent::Disconnected(id) => {
+                self.pads.remove(&id);
+            }
+            GamepadEvent::ButtonPressed(id, button) => {
+                self.pads.entry(id).or_default().buttons.insert(button);
+            }
+            GamepadEvent::ButtonReleased(id, button) => {
+                self.pads.entry(id).or_default().buttons.remove(&button);
+            }
+            GamepadEvent::AxisMoved(id, axis, value) => {
+                self.pads.entry(id).or_default().axes.insert(axis, value);
+            }
+        }
+    }
+
+    fn process(&mut self, event: GamepadEvent, events: &mut Vec<GamepadEvent>) {
+        match event {
+            GamepadEvent::Connected(id) => {
+                self.pads.insert(id, GamepadState::default());
+                events.push(event);
+            }
+            GamepadEvent::Disconnected(id) => {
+                if self.pads.remove(&id).is_some() {
+                    events.push(event);
+                }
+            }
+            GamepadEvent::ButtonPressed(id, button) => {
+                if self.pads.entry(id).or_default().buttons.insert(button) {
+                    events.push(event);
+                }
+            }
+            GamepadEvent::ButtonReleased(id, button) => {
+                if self.pads.entry(id).or_default().buttons.remove(&button) {
+                    events.push(event);
+                }
+            }
+            GamepadEvent::AxisMoved(id, axis, value) => {
+                let stick_deadzone = self.stick_deadzone;
+                let trigger_deadzone = self.trigger_deadzone;
+
+                let pad = self.pads.entry(id).or_default();
+                pad.raw_axes.insert(axis, value);
+
+                let filtered = match axis.stick_pair() {
+                    Some((x_axis, y_axis)) => {
+                        let (x, y) = apply_radial_deadzone(
+                            pad.raw_axis(x_axis),
+                            pad.raw_axis(y_axis),
+                            stick_deadzone,
+                        );
+                        vec![(x_axis, x), (y_axis, y)]
+                    }
+                    None => vec![(axis, apply_deadzone(value, trigger_deadzone))],
+                };
+
+                for (axis, value) in filtered {
+                    if pad.axis(axis) != value {
+                        pad.axes.insert(axis, value);
+                        events.push(GamepadEvent::AxisMoved(id, axis, value));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Default for Gamepads {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn apply_deadzone(value: f32, deadzone: f32) -> f32 {
+    if value.abs() <= deadzone {
+        0.0
+    } else {
+        value.signum() * ((value.abs() - deadzone) / (1.0 - deadzone)).min(1.0)
+    }
+}
+
+fn apply_radial_deadzone(x: f32, y: f32, deadzone: f32) -> (f32, f32) {
+    let magnitude = (x * x + y * y).sqrt();
+    if magnitude <= deadzone {
+        (0.0, 0.0)
+    } else {
+        let scaled = ((magnitude - deadzone) / (1.0 - deadzone)).min(1.0);
+        (x / magnitude * scaled, y / magnitude * scaled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAD: GamepadId = GamepadId(0);
+
+    fn gamepads() -> (Gamepads, VirtualGamepad) {
+        let virtual_pad = VirtualGamepad::new();
+        let mut gamepads = Gamepads::new();
+        gamepads.set_backend(virtual_pad.clone());
+        virtual_pad.connect(PAD);
+        gamepads.poll_events();
+        (gamepads, virtual_pad)
+    }
+
+    #[test]
+    fn stick_inside_radial_deadzone_is_zero() {
+        let (mut gamepads, pad) = gamepads();
+        // each axis alone is inside the deadzone and so is their length
+        pad.set_axis(PAD, Axis::LeftStickX, 0.1);
+        pad.set_axis(PAD, Axis::LeftStickY, 0.1);
+        assert!(gamepads.poll_events().is_empty());
+        assert_eq!(gamepads.axis(PAD, Axis::LeftStickX), 0.0);
+        assert_eq!(gamepads.axis(PAD, Axis::LeftStickY), 0.0);
+    }
+
+    #[test]
+    fn stick_outside_radial_deadzone_is_rescaled() {
+        let (mut gamepads, pad) = gamepads();
+        // each axis alone is inside the deadzone, but the diagonal isn't
+        pad.set_axis(PAD, Axis::LeftStickX, 0.12);
+        pad.set_axis(PAD, Axis::LeftStickY, 0.12);
+        gamepads.poll_events();
+        let x = gamepads.axis(PAD, Axis::LeftStickX);
+        let y = gamepads.axis(PAD, Axis::LeftStickY);
+        assert!(x > 0.0 && (x - y).abs() < 1e-6);
+
+        pad.set_axis(PAD, Axis::LeftStickX, 1.0);
+        pad.set_axis(PAD, Axis::LeftStickY, 0.0);
+        gamepads.poll_events();
+        assert_eq!(gamepads.axis(PAD, Axis::LeftStickX), 1.0);
+        assert_eq!(gamepads.axis(PAD, Axis::LeftStickY), 0.0);
+    }
+
+    #[test]
+    fn trigger_deadzone() {
+        let (mut gamepads, pad) = gamepads();
+        pad.set_axis(PAD, Axis::LeftTrigger, 0.04);
+        assert!(gamepads.poll_events().is_empty());
+        assert_eq!(gamepads.axis(PAD, Axis::LeftTrigger), 0.0);
+
+        pad.set_axis(PAD, Axis::LeftTrigger, 0.525);
+        gamepads.poll_events();
+        assert!((gamepads.axis(PAD, Axis::LeftTrigger) - 0.5).abs() < 1e-6);
+
+        pad.set_axis(PAD, Axis::LeftTrigger, 1.0);
+        gamepads.poll_events();
+        assert_eq!(gamepads.axis(PAD, Axis::LeftTrigger), 1.0);
+    }
+
+    #[test]
+    fn repeated_presses_and_releases_are_reported_once() {
+        let (mut gamepads, pad) = gamepads();
+        pad.press(PAD, Button::South);
+        pad.press(PAD, Button::South);
+        assert_eq!(
+            gamepads.poll_events(),
+            vec![GamepadEvent::ButtonPressed(PAD, Button::South)]
+        );
+        assert!(gamepads.is_pressed(PAD, Button::South));
+
+        pad.release(PAD, Button::South);
+        pad.release(PAD, Button::South);
+        pad.release(PAD, Button::East);
+        assert_eq!(
+            gamepads.poll_events(),
+            vec![GamepadEvent::ButtonReleased(PAD, Button::South)]
+        );
+        assert!(!gamepads.is_pressed(PAD, Button::South));
+    }
+
+    #[test]
+    fn disconnect_drops_state() {
+        let (mut gamepads, pad) = gamepads();
+        pad.press(PAD, Button::Start);
+        pad.set_axis(PAD, Axis::RightTrigger, 1.0);
+        pad.disconnect(PAD);
+        let events = gamepads.poll_events();
+        assert_eq!(events.last(), Some(&GamepadEvent::Disconnected(PAD)));
+        assert!(gamepads.state(PAD).is_none());
+        assert!(!gamepads.is_pressed(PAD, Button::Start));
+        assert_eq!(gamepads.axis(PAD, Axis::RightTrigger), 0.0);
+        assert_eq!(gamepads.connected().count(), 0);
+
+        // a pad that isn't connected can't be disconnected again
+        pad.disconnect(PAD);
+        pad.disconnect(GamepadId(7));
+        assert!(gamepads.poll_events().is_empty());
+    }
+
+    #[test]
+    fn unchanged_axis_values_are_filtered() {
+        let (mut gamepads, pad) = gamepads();
+        pad.set_axis(PAD, Axis::RightTrigger, 1.0);
+        pad.set_axis(PAD, Axis::RightTrigger, 1.0);
+        pad.set_axis(PAD, Axis::RightTrigger, 1.0);
+        assert_eq!(
+            gamepads.poll_events(),
+            vec![GamepadEvent::AxisMoved(PAD, Axis::RightTrigger, 1.0)]
+        );
+
+        // moving one stick axis reports only the axis whose filtered value changed
+        pad.set_axis(PAD, Axis::RightStickX, 1.0);
+        assert_eq!(
+            gamepads.poll_events(),
+            vec![GamepadEvent::AxisMoved(PAD, Axis::RightStickX, 1.0)]
+        );
+        assert!(gamepads.poll_events().is_empty());
+    }
+
+    #[test]
+    fn applied_events_reproduce_the_state() {
+        let (mut gamepads, pad) = gamepads();
+        pad.press(PAD, Button::South);
+        pad.set_axis(PAD, Axis::LeftStickX, 0.6);
+        pad.set_axis(PAD, Axis::LeftTrigger, 0.3);
+        let mut events = vec![GamepadEvent::Connected(PAD)];
+        events.extend(gamepads.poll_events());
+
+        // the recorded values are past the deadzones, so they mustn't be filtered again
+        let mut replayed = Gamepads::new();
+        replayed.set_backend(VirtualGamepad::new());
+        for &event in &events {
+            replayed.apply(event);
+        }
+        assert!(replayed.is_pressed(PAD, Button::South));
+        for &axis in &[Axis::LeftStickX, Axis::LeftStickY, Axis::LeftTrigger] {
+            assert_eq!(replayed.axis(PAD, axis), gamepads.axis(PAD, axis));
+        }
+
+        replayed.apply(GamepadEvent::ButtonReleased(PAD, Button::South));
+        assert!(!replayed.is_pressed(PAD, Button::South));
+        replayed.apply(GamepadEvent::Disconnected(PAD));
+        assert_eq!(replayed.connected().count(), 0);
+    }
+
+    #[test]
+    fn discarded_events_dont_change_the_state() {
+        let (mut gamepads, pad) = gamepads();
+        pad.press(PAD, Button::North);
+        gamepads.discard_events();
+        assert!(!gamepads.is_pressed(PAD, Button::North));
+        assert!(gamepads.poll_events().is_empty());
+    }
+}
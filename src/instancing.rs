@@ -0,0 +1,67 @@
+use crate::error::PumiceResult;
+use crate::{GraphicsContext, Mesh};
+
+/// One copy of a mesh drawn by `draw_instanced`.
+#[derive(Default, Copy, Clone, Debug)]
+pub struct MeshInstance {
+    pub position: [f32; 2],
+    pub scale: [f32; 2],
+    // degrees, around `position`
+    pub rotation: f32,
+    pub color: [f32; 4],
+}
+
+vulkano::impl_vertex!(MeshInstance, position, scale, rotation, color);
+
+impl MeshInstance {
+    pub fn new(position: [f32; 2], color: [f32; 4]) -> Self {
+        MeshInstance {
+            position,
+            scale: [1.0, 1.0],
+            rotation: 0.0,
+            color,
+        }
+    }
+}
+
+#[derive(Default, Copy, Clone, Debug)]
+pub(crate) struct MeshVertex {
+    pub(crate) mesh_position: [f32; 2],
+}
+
+vulkano::impl_vertex!(MeshVertex, mesh_position);
+
+// a mesh and everything it's drawn at this frame, uploaded as one draw call
+pub(crate) struct InstanceBatch {
+    // how many indices the regular geometry had when this was added, it's drawn after those
+    pub(crate) first_index: usize,
+    pub(crate) vertices: Vec<MeshVertex>,
+    pub(crate) indices: Vec<u16>,
+    pub(crate) instances: Vec<MeshInstance>,
+}
+
+impl GraphicsContext {
+    // Draws `mesh` once for every instance with a single draw call, for when there are too many
+    // copies of a shape to tessellate them all each frame. Instanced meshes are drawn in order with
+    // everything else, over what was drawn before and under what's drawn after.
+    pub fn draw_instanced(&mut self, mesh: &Mesh, instances: &[MeshInstance]) -> PumiceResult<()> {
+        if instances.is_empty() || mesh.index_count() == 0 {
+            return Ok(());
+        }
+
+        let geometry = mesh.geometry();
+        self.instance_batches.push(InstanceBatch {
+            first_index: self.geometry.indices.len(),
+            vertices: geometry
+                .vertices
+                .iter()
+                .map(|p| MeshVertex {
+                    mesh_position: [p.x, p.y],
+                })
+                .collect(),
+            indices: geometry.indices.clone(),
+            instances: instances.to_vec(),
+        });
+        Ok(())
+    }
+}
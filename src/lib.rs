@@ -1,27 +1,13 @@
 pub use winit;
 
-use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
-use vulkano::device::{Device, DeviceExtensions, Features, Queue};
-use vulkano::instance::{Instance, PhysicalDevice};
-
-use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
-use vulkano::command_buffer::AutoCommandBufferBuilder;
-
-use vulkano::sync::GpuFuture;
+use vulkano::device::{Device, Queue};
+use vulkano::instance::debug::DebugCallback;
+use vulkano::instance::{Instance, InstanceExtensions, PhysicalDevice};
 
 use std::sync::Arc;
+use std::time::Instant;
 
-use vulkano::framebuffer::{Framebuffer, FramebufferAbstract, RenderPassAbstract};
-
-use vulkano::framebuffer::Subpass;
-use vulkano::pipeline::GraphicsPipeline;
-
-use vulkano::command_buffer::DynamicState;
-use vulkano::pipeline::viewport::Viewport;
-
-use vulkano::buffer::CpuBufferPool;
-use vulkano::image::swapchain::SwapchainImage;
-use vulkano::swapchain::{PresentMode, SurfaceTransform, Swapchain};
+use vulkano::swapchain::PresentMode;
 
 use lyon::math::Point;
 use lyon::path::Path;
@@ -34,6 +20,8 @@ use lyon::tessellation::BuffersBuilder;
 use lyon::tessellation::FillAttributes;
 use lyon::tessellation::FillTessellator;
 use lyon::tessellation::FillVertexConstructor;
+use lyon::tessellation::StrokeAttributes;
+use lyon::tessellation::StrokeVertexConstructor;
 use lyon::tessellation::{FillOptions, VertexBuffers};
 
 use vulkano_win::VkSurfaceBuild;
@@ -42,55 +30,138 @@ use winit::EventsLoop;
 use winit::Window;
 use winit::WindowBuilder;
 
-use vulkano::swapchain::AcquireError;
-use vulkano::swapchain::SwapchainCreationError;
-use vulkano::sync::FlushError;
-
 pub mod error;
 pub use error::{PumiceError, PumiceResult};
 
+pub mod animation;
+pub use animation::{Animation, Clip, PlayMode};
+
+pub mod builder;
+pub use builder::ContextBuilder;
+
+pub mod collision;
+
+pub mod debug;
+
+pub mod device;
+use device::{DeviceInfo, DevicePreference};
+
+pub mod event;
+pub use event::Event;
+
+mod feather;
+
+pub mod fullscreen;
+use fullscreen::{FullscreenMode, WindowedGeometry};
+
+pub mod gamepad;
+use gamepad::Gamepads;
+
+pub mod instancing;
+use instancing::InstanceBatch;
+pub use instancing::MeshInstance;
+
+pub mod limiter;
+use limiter::FrameLimiter;
+
+mod renderer;
+use renderer::{Renderer, Shaders};
+
+pub mod report;
+use report::{ErrorAction, ErrorOrigin, ErrorReporter};
+
+pub mod mesh;
+use mesh::MeshCache;
+pub use mesh::{Mesh, TessellationQuality};
+
+pub mod paint;
+pub use paint::Paint;
+
+#[cfg(feature = "physics")]
+pub mod physics;
+
+pub mod particles;
+pub use particles::{ParticleEmitter, ParticleShape};
+
+pub mod replay;
+use replay::{Recorder, Replay};
+
+pub mod shapes;
+pub use shapes::Style;
+
+pub mod spatial;
+pub use spatial::SpatialHash;
+
+pub mod stats;
+use stats::{FrameStats, StatsCollector};
+
+pub mod text;
+use text::TextEvent;
+
+pub mod tween;
+pub use tween::{Easing, Tween};
+
 #[derive(Default, Copy, Clone, Debug)]
 pub struct Vertex {
     pub position: [f32; 2],
     pub color: [f32; 4],
     pub rot: [f32; 3], //x, y, degrees
+    // the rest is only used by gradients, see Paint
+    pub gradient_color: [f32; 4],
+    pub gradient: [f32; 4],
+    pub gradient_type: f32,
 }
 
-vulkano::impl_vertex!(Vertex, position, color, rot);
+vulkano::impl_vertex!(
+    Vertex,
+    position,
+    color,
+    rot,
+    gradient_color,
+    gradient,
+    gradient_type
+);
 
-struct WithColor([f32; 4]);
+struct WithPaint(Paint);
 
-impl BasicVertexConstructor<Vertex> for WithColor {
+impl BasicVertexConstructor<Vertex> for WithPaint {
     fn new_vertex(&mut self, position: Point) -> Vertex {
-        Vertex {
-            position: [position.x, position.y],
-            color: self.0,
-            rot: [0.0, 0.0, 0.0],
-        }
+        self.0.vertex([position.x, position.y], [0.0, 0.0, 0.0])
     }
 }
 
-impl FillVertexConstructor<Vertex> for WithColor {
+impl FillVertexConstructor<Vertex> for WithPaint {
     fn new_vertex(&mut self, position: Point, mut attributes: FillAttributes) -> Vertex {
-        Vertex {
-            position: [position.x, position.y],
-            color: self.0,
-            rot: [0.0, 0.0, 0.0],
-        }
+        self.0.vertex([position.x, position.y], [0.0, 0.0, 0.0])
     }
 }
 
-struct WithColorRotCenter([f32; 4], [f32; 3]);
-impl BasicVertexConstructor<Vertex> for WithColorRotCenter {
+impl StrokeVertexConstructor<Vertex> for WithPaint {
+    fn new_vertex(&mut self, position: Point, _attributes: StrokeAttributes) -> Vertex {
+        self.0.vertex([position.x, position.y], [0.0, 0.0, 0.0])
+    }
+}
+
+struct WithPaintRotCenter(Paint, [f32; 3]);
+impl BasicVertexConstructor<Vertex> for WithPaintRotCenter {
     fn new_vertex(&mut self, position: Point) -> Vertex {
-        Vertex {
-            position: [position.x, position.y],
-            color: self.0,
-            rot: self.1,
-        }
+        self.0.vertex([position.x, position.y], self.1)
+    }
+}
+
+impl FillVertexConstructor<Vertex> for WithPaintRotCenter {
+    fn new_vertex(&mut self, position: Point, _attributes: FillAttributes) -> Vertex {
+        self.0.vertex([position.x, position.y], self.1)
+    }
+}
+
+impl StrokeVertexConstructor<Vertex> for WithPaintRotCenter {
+    fn new_vertex(&mut self, position: Point, _attributes: StrokeAttributes) -> Vertex {
+        self.0.vertex([position.x, position.y], self.1)
     }
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Rotation {
     pub degrees: f32,
     pub point: [f32; 2],
@@ -103,6 +174,13 @@ mod vs {
     }
 }
 
+mod ivs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/instanced_vertex.glsl",
+    }
+}
+
 mod fs {
     vulkano_shaders::shader! {
         ty: "fragment",
@@ -110,82 +188,330 @@ mod fs {
     }
 }
 
+enum FrameResult {
+    // there was nothing to draw to, update wasn't called
+    Skipped,
+    Drawn,
+    // update failed and the error handler asked to stop
+    Stopped(PumiceError),
+}
+
+// consecutive device or surface losses to recover from before `run` gives up
+const MAX_RECOVERIES: u32 = 3;
+
+// see ContextBuilder::on_device_recreated
+pub(crate) type DeviceRecreatedHook = Box<dyn FnMut(&mut GraphicsContext)>;
+
+// longest frame `delta_time` will report, in seconds
+const MAX_DELTA_TIME: f32 = 0.1;
+
 pub struct GraphicsContext {
     instance: Arc<Instance>,
+    // validation messages stop as soon as this is dropped
+    _debug_callback: Option<DebugCallback>,
     device: Arc<Device>,
+    device_info: DeviceInfo,
     queue: Arc<Queue>,
-    dynamic_state: DynamicState,
-    vertex_shader: vs::Shader,
-    fragment_shader: fs::Shader,
+    // set for the first update after the device was lost and recreated
+    pub device_recreated: bool,
+    device_recreated_hook: Option<DeviceRecreatedHook>,
+    shaders: Shaders,
     geometry: VertexBuffers<Vertex, u16>,
+    // drawn in between `geometry`, see draw_instanced
+    instance_batches: Vec<InstanceBatch>,
     pub surface: Arc<vulkano::swapchain::Surface<Window>>,
+    // kept to rebuild the window if the surface is lost
+    window_builder: WindowBuilder,
     events_loop: EventsLoop,
     pub screen_maxes: [f32; 2],
     pub screen_size_changed: bool,
+    pub gamepads: Gamepads,
+    frame: u64,
+    recorder: Option<Recorder>,
+    replay: Option<Replay>,
+    // the recorded frame time `delta_time` reports while replaying
+    replay_delta_time: Option<f32>,
+    text_input: Vec<TextEvent>,
+    requested_present_mode: PresentMode,
+    active_present_mode: PresentMode,
+    present_mode_changed: bool,
+    frame_limiter: FrameLimiter,
+    samples: u32,
+    pub clear_color: [f32; 4],
+    fullscreen_mode: FullscreenMode,
+    last_fullscreen_mode: FullscreenMode,
+    windowed_geometry: Option<WindowedGeometry>,
+    window_mode_changed: bool,
+    pub alt_enter_fullscreen: bool,
+    errors: ErrorReporter,
+    stats: StatsCollector,
+    // draws `stats()` in the top left corner
+    pub stats_overlay: bool,
+    // fades shape edges out over a pixel, see ContextBuilder::feathered_edges
+    pub feathered_edges: bool,
+    mesh_cache: MeshCache,
+    pub tessellation_quality: TessellationQuality,
+    // in physical pixels, updated every frame so shapes don't have to ask the window
+    window_size: [f32; 2],
 }
 
 impl GraphicsContext {
     pub fn new() -> Self {
-        let instance = {
-            let extensions = vulkano_win::required_extensions();
-            match Instance::new(None, &extensions, None) {
-                Ok(i) => i,
-                Err(e) => panic!("Error creating instance {}", e),
+        ContextBuilder::new().build()
+    }
+
+    pub fn try_new() -> PumiceResult<Self> {
+        ContextBuilder::new().try_build()
+    }
+
+    fn from_builder(mut builder: ContextBuilder) -> PumiceResult<Self> {
+        let instance = match &builder.debug {
+            Some(_) => {
+                let extensions = InstanceExtensions {
+                    ext_debug_utils: true,
+                    ..vulkano_win::required_extensions()
+                };
+                Instance::new(None, &extensions, debug::validation_layers())?
+            }
+            None => {
+                let extensions = vulkano_win::required_extensions();
+                Instance::new(None, &extensions, None)?
             }
         };
-        let physical = PhysicalDevice::enumerate(&instance)
-            .next()
-            .expect("no device available");
-
-        let queue_family = physical
-            .queue_families()
-            .find(|&q| q.supports_graphics())
-            .expect("couldn't find a graphical queue family");
-        let (device, mut queues) = {
-            Device::new(
-                physical,
-                &Features::none(),
-                &DeviceExtensions {
-                    khr_storage_buffer_storage_class: true,
-                    khr_swapchain: true,
-                    ..DeviceExtensions::none()
-                },
-                [(queue_family, 0.5)].iter().cloned(),
-            )
-            .expect("failed to create device")
+        let debug_callback = builder
+            .debug
+            .as_ref()
+            .and_then(|options| debug::create_callback(&instance, options));
+        let events_loop = EventsLoop::new();
+        let fullscreen_mode = if builder.fullscreen {
+            FullscreenMode::Fullscreen(None)
+        } else {
+            FullscreenMode::Windowed
         };
+        let window_builder = builder.window_builder(&events_loop);
+        let surface = window_builder
+            .clone()
+            .build_vk_surface(&events_loop, instance.clone())?;
+
+        let preference = DevicePreference::from_env().unwrap_or(builder.device_preference);
+        let (physical, queue_family) =
+            device::select_physical_device(&instance, &surface, &preference)
+                .ok_or(PumiceError::NoSuitableDevice)?;
+        let device_info = DeviceInfo::new(physical);
+        let samples = device::clamp_samples(builder.samples, &device_info.limits);
+        if samples != builder.samples {
+            log::warn!(
+                "{}x MSAA isn't supported by {}, using {}x",
+                builder.samples,
+                device_info.name,
+                samples
+            );
+        }
+        let (device, queue) = device::create_device(physical, queue_family)?;
 
-        let queue = queues.next().unwrap();
-
-        let vs = vs::Shader::load(device.clone()).expect("failed to load vertex shader");
-        let fs = fs::Shader::load(device.clone()).expect("failed to load fragment shader");
+        let shaders = Shaders::load(device.clone())?;
 
-        let dynamic_state = DynamicState {
-            viewports: Some(vec![Viewport {
-                origin: [0.0, 0.0],
-                dimensions: [1024.0, 1024.0],
-                depth_range: 0.0..1.0,
-            }]),
-            ..DynamicState::none()
-        };
+        let window_size = window_size(surface.window());
 
-        let events_loop = EventsLoop::new();
-        let surface = WindowBuilder::new()
-            .build_vk_surface(&events_loop, instance.clone())
-            .unwrap();
-
-        GraphicsContext {
+        Ok(GraphicsContext {
             instance,
+            _debug_callback: debug_callback,
             device,
+            device_info,
             queue,
-            dynamic_state,
-            vertex_shader: vs,
-            fragment_shader: fs,
+            device_recreated: false,
+            device_recreated_hook: builder.device_recreated_hook.take(),
+            shaders,
+            instance_batches: Vec::new(),
             geometry: VertexBuffers::new(),
             surface,
+            window_builder,
             events_loop,
             screen_maxes: [1.0, 1.0],
             screen_size_changed: true,
+            gamepads: Gamepads::new(),
+            frame: 0,
+            recorder: None,
+            replay: None,
+            replay_delta_time: None,
+            text_input: Vec::new(),
+            requested_present_mode: builder.present_mode,
+            active_present_mode: builder.present_mode,
+            present_mode_changed: false,
+            frame_limiter: FrameLimiter::new(builder.target_fps),
+            samples,
+            clear_color: builder.clear_color,
+            fullscreen_mode,
+            last_fullscreen_mode: FullscreenMode::Fullscreen(None),
+            windowed_geometry: None,
+            window_mode_changed: false,
+            alt_enter_fullscreen: builder.alt_enter_fullscreen,
+            errors: ErrorReporter::new(builder.error_handler.take()),
+            stats: StatsCollector::new(),
+            stats_overlay: builder.stats_overlay,
+            feathered_edges: builder.feathered_edges,
+            mesh_cache: MeshCache::new(),
+            tessellation_quality: builder.tessellation_quality,
+            window_size,
+        })
+    }
+
+    // how many errors from `origin` `run` has seen so far
+    pub fn error_count(&self, origin: ErrorOrigin) -> u32 {
+        self.errors.count(origin)
+    }
+
+    // the MSAA sample count in use, which can be lower than the one asked for
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    // timings for the last frame `run` drew
+    pub fn stats(&self) -> &FrameStats {
+        self.stats.stats()
+    }
+
+    pub fn device_info(&self) -> &DeviceInfo {
+        &self.device_info
+    }
+
+    // the present mode actually in use, which can differ from the one asked for when the
+    // surface doesn't support it
+    pub fn present_mode(&self) -> PresentMode {
+        self.active_present_mode
+    }
+
+    pub fn set_present_mode(&mut self, present_mode: PresentMode) {
+        self.requested_present_mode = present_mode;
+        self.present_mode_changed = true;
+    }
+
+    pub fn target_fps(&self) -> Option<f64> {
+        self.frame_limiter.target_fps()
+    }
+
+    pub fn set_target_fps(&mut self, target_fps: Option<f64>) {
+        self.frame_limiter.set_target_fps(target_fps);
+    }
+
+    pub fn monitors(&self) -> Vec<winit::MonitorId> {
+        self.events_loop.get_available_monitors().collect()
+    }
+
+    pub fn fullscreen_mode(&self) -> &FullscreenMode {
+        &self.fullscreen_mode
+    }
+
+    // the swapchain is recreated at the start of the next frame
+    pub fn set_fullscreen(&mut self, mode: FullscreenMode) {
+        let window = self.surface.window();
+        if self.fullscreen_mode.is_windowed() {
+            self.windowed_geometry = Some(WindowedGeometry::save(window));
+        } else {
+            self.last_fullscreen_mode = self.fullscreen_mode.clone();
+        }
+
+        fullscreen::apply(window, &mode, self.windowed_geometry);
+        self.fullscreen_mode = mode;
+        self.window_mode_changed = true;
+    }
+
+    // switches between windowed and whichever fullscreen mode was used last
+    pub fn toggle_fullscreen(&mut self) {
+        if self.fullscreen_mode.is_windowed() {
+            self.set_fullscreen(self.last_fullscreen_mode.clone());
+        } else {
+            self.set_fullscreen(FullscreenMode::Windowed);
+        }
+    }
+
+    // text typed since the last frame, separate from key presses
+    pub fn text_input(&self) -> &[TextEvent] {
+        &self.text_input
+    }
+
+    // moves the IME candidate window to a point in game coordinates, e.g. under a text field
+    pub fn set_ime_position(&self, pos: [f32; 2]) {
+        let window = self.surface.window();
+        if let Some(size) = window.get_inner_size() {
+            let x = (pos[0] / self.screen_maxes[0] + 1.0) / 2.0 * size.width as f32;
+            let y = (pos[1] / self.screen_maxes[1] + 1.0) / 2.0 * size.height as f32;
+            window.set_ime_spot(winit::dpi::LogicalPosition::new(x as f64, y as f64));
+        }
+    }
+
+    // the number of times `run` has called update, which is what recorded events are keyed by
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    // Seconds since the previous frame, for moving things at the same speed whatever the frame
+    // rate. Capped so a long stall (dragging the window, a breakpoint) doesn't teleport anything.
+    // While replaying it's the recorded frame time, so the game steps the same way it did.
+    pub fn delta_time(&self) -> f32 {
+        if let Some(delta_time) = self.replay_delta_time {
+            return delta_time;
+        }
+        self.stats
+            .stats()
+            .frame_time
+            .as_secs_f32()
+            .min(MAX_DELTA_TIME)
+    }
+
+    pub fn record_events(&mut self, path: impl AsRef<std::path::Path>) -> PumiceResult<()> {
+        self.recorder = Some(Recorder::create(path)?);
+        Ok(())
+    }
+
+    // Feeds the events in a recording to `handle_event` instead of live input, updating
+    // `gamepads` from the recorded gamepad events, and plays back the recorded `delta_time`s.
+    // Live input is dropped until the replay is done, except that the window can still be closed
+    // and resized.
+    pub fn replay_events(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+        exit_when_finished: bool,
+    ) -> PumiceResult<()> {
+        let mut replay = Replay::load(path)?;
+        replay.exit_when_finished = exit_when_finished;
+        self.replay = Some(replay);
+        Ok(())
+    }
+
+    // the width and height of a pixel in game coordinates, which only differ when `screen_maxes`
+    // doesn't match the window's aspect ratio
+    fn pixel_size(&self) -> Option<[f32; 2]> {
+        let [width, height] = self.window_size;
+        if width > 0.0 && height > 0.0 {
+            Some([
+                self.screen_maxes[0] * 2.0 / width,
+                self.screen_maxes[1] * 2.0 / height,
+            ])
+        } else {
+            None
+        }
+    }
+
+    // how far tessellated curves can be from the real shape, in game coordinates
+    pub fn tolerance(&self) -> f32 {
+        // the smaller side, so curves stay smooth along both axes
+        let pixel_size = self.pixel_size().map(|[width, height]| width.min(height));
+        self.tessellation_quality.tolerance(pixel_size)
+    }
+
+    // Called after each shape is added to the geometry. Outlines aren't feathered, the fringe pulls
+    // both sides of a line in by half a pixel and thin lines would all but disappear.
+    fn finish_shape(&mut self, first_index: usize, style: Style) -> PumiceResult<()> {
+        if !self.feathered_edges || style != Style::Fill {
+            return Ok(());
+        }
+        // the larger side, so the fringe covers at least a pixel along both axes
+        match self.pixel_size() {
+            Some([width, height]) => {
+                feather::add_fringe(&mut self.geometry, first_index, width.max(height))
+            }
+            None => Ok(()),
         }
     }
 
@@ -193,23 +519,23 @@ impl GraphicsContext {
         &mut self,
         pos: impl Into<Point>,
         rad: f32,
-        color: [f32; 4],
+        paint: impl Into<Paint>,
     ) -> PumiceResult<()> {
-        let options = FillOptions::tolerance(0.0001);
-        let mut buffer_builder = BuffersBuilder::new(&mut self.geometry, WithColor(color));
-        match basic_shapes::fill_circle(pos.into(), rad, &options, &mut buffer_builder) {
-            Ok(_) => Ok(()),
-            Err(tesselate_error) => Err(PumiceError::from(tesselate_error)),
-        }
+        let pos = pos.into();
+        let first_index = self.geometry.indices.len();
+        let tolerance = self.tolerance();
+        let mesh = self.mesh_cache.circle(rad, tolerance)?;
+        mesh.append_to(&mut self.geometry, [pos.x, pos.y], 1.0, paint.into(), None)?;
+        self.finish_shape(first_index, Style::Fill)
     }
 
     pub fn new_rectangle(
         &mut self,
         pos: impl Into<Point>,
         sides: impl Into<Size>,
-        color: [f32; 4],
+        paint: impl Into<Paint>,
     ) -> PumiceResult<()> {
-        self.new_rectangle_full(pos, sides, color, None)
+        self.new_rectangle_full(pos, sides, paint, None)
     }
 
     pub fn new_rectangle_rotcenter(
@@ -217,14 +543,14 @@ impl GraphicsContext {
         pos: impl Into<Point>,
         sides: impl Into<Size>,
         rot: f32,
-        color: [f32; 4],
+        paint: impl Into<Paint>,
     ) -> PumiceResult<()> {
         let point = pos.into();
         let sides = sides.into();
         self.new_rectangle_full(
             point,
             sides,
-            color,
+            paint,
             Some(Rotation {
                 degrees: rot,
                 point: [point.x + sides.width / 2.0, point.y + sides.height / 2.0],
@@ -236,36 +562,37 @@ impl GraphicsContext {
         &mut self,
         pos: impl Into<Point>,
         sides: impl Into<Size>,
-        color: [f32; 4],
+        paint: impl Into<Paint>,
         rot: Option<Rotation>, //degrees
     ) -> PumiceResult<()> {
+        let first_index = self.geometry.indices.len();
         let options = FillOptions::non_zero();
         let rect = Rect::new(pos.into(), sides.into());
-        if let Some(rot) = rot {
+        let result = if let Some(rot) = rot {
             let mut buffer_builder = BuffersBuilder::new(
                 &mut self.geometry,
-                WithColorRotCenter(color, [rot.point[0], rot.point[1], rot.degrees]),
+                WithPaintRotCenter(paint.into(), [rot.point[0], rot.point[1], rot.degrees]),
             );
-            match basic_shapes::fill_rectangle(&rect, &options, &mut buffer_builder) {
-                Ok(_) => Ok(()),
-                Err(tesselate_error) => Err(PumiceError::from(tesselate_error)),
-            }
+            basic_shapes::fill_rectangle(&rect, &options, &mut buffer_builder)
         } else {
-            let mut buffer_builder = BuffersBuilder::new(&mut self.geometry, WithColor(color));
-            match basic_shapes::fill_rectangle(&rect, &options, &mut buffer_builder) {
-                Ok(_) => Ok(()),
-                Err(tesselate_error) => Err(PumiceError::from(tesselate_error)),
-            }
+            let mut buffer_builder =
+                BuffersBuilder::new(&mut self.geometry, WithPaint(paint.into()));
+            basic_shapes::fill_rectangle(&rect, &options, &mut buffer_builder)
+        };
+        match result {
+            Ok(_) => self.finish_shape(first_index, Style::Fill),
+            Err(tesselate_error) => Err(PumiceError::from(tesselate_error)),
         }
     }
 
     pub fn new_quad(
         &mut self,
         points: [impl Into<Point> + Copy; 4],
-        color: [f32; 4],
+        paint: impl Into<Paint>,
     ) -> PumiceResult<()> {
+        let first_index = self.geometry.indices.len();
         let options = FillOptions::non_zero();
-        let mut buffer_builder = BuffersBuilder::new(&mut self.geometry, WithColor(color));
+        let mut buffer_builder = BuffersBuilder::new(&mut self.geometry, WithPaint(paint.into()));
         let result = basic_shapes::fill_quad(
             points[0].into(),
             points[1].into(),
@@ -275,14 +602,19 @@ impl GraphicsContext {
             &mut buffer_builder,
         );
         match result {
-            Ok(_) => Ok(()),
+            Ok(_) => self.finish_shape(first_index, Style::Fill),
             Err(tesselate_error) => Err(PumiceError::from(tesselate_error)),
         }
     }
 
-    pub fn new_triangle(&mut self, points: [impl Into<Point> + Copy; 3], color: [f32; 4]) {
+    pub fn new_triangle(
+        &mut self,
+        points: [impl Into<Point> + Copy; 3],
+        paint: impl Into<Paint>,
+    ) -> PumiceResult<()> {
+        let first_index = self.geometry.indices.len();
         let options = FillOptions::default();
-        let mut buffer_builder = BuffersBuilder::new(&mut self.geometry, WithColor(color));
+        let mut buffer_builder = BuffersBuilder::new(&mut self.geometry, WithPaint(paint.into()));
 
         let mut path_builder = Path::builder();
         path_builder.move_to(points[0].into());
@@ -293,218 +625,281 @@ impl GraphicsContext {
         let path = path_builder.build();
 
         let mut tesselator = FillTessellator::new();
-        tesselator.tessellate_with_ids(
+        let result = tesselator.tessellate_with_ids(
             path.id_iter(),
             &path,
             Some(&path),
             &options,
             &mut buffer_builder,
         );
+        match result {
+            Ok(_) => self.finish_shape(first_index, Style::Fill),
+            Err(tesselate_error) => Err(PumiceError::from(tesselate_error)),
+        }
     }
 
-    pub fn run<D>(
-        mut self,
-        data: &mut D,
-        update: &dyn Fn(&mut GraphicsContext, &mut D) -> PumiceResult<()>,
-        handle_event: &dyn Fn(&winit::Event, &mut D) -> PumiceResult<()>,
-        clear_color: [f32; 4],
+    // each corner gets its own color, blended across the quad
+    pub fn new_quad_colors(
+        &mut self,
+        points: [impl Into<Point> + Copy; 4],
+        colors: [[f32; 4]; 4],
     ) -> PumiceResult<()> {
-        let mut recreate_swapchain = false;
-        let physical = PhysicalDevice::enumerate(&self.instance)
-            .next()
-            .expect("no device available");
-
-        let caps = self
-            .surface
-            .capabilities(physical)
-            .expect("failed to get surface capabilities");
-        let dimensions = caps.current_extent.unwrap_or([1280, 1024]);
-        let alpha = caps.supported_composite_alpha.iter().next().unwrap();
-        let format = caps.supported_formats[0].0;
-
-        let (mut swapchain, images) = Swapchain::new(
+        self.new_polygon_colors(&[
+            (points[0].into(), colors[0]),
+            (points[1].into(), colors[1]),
+            (points[2].into(), colors[2]),
+            (points[3].into(), colors[3]),
+        ])
+    }
+
+    pub fn new_triangle_colors(
+        &mut self,
+        points: [impl Into<Point> + Copy; 3],
+        colors: [[f32; 4]; 3],
+    ) -> PumiceResult<()> {
+        self.new_polygon_colors(&[
+            (points[0].into(), colors[0]),
+            (points[1].into(), colors[1]),
+            (points[2].into(), colors[2]),
+        ])
+    }
+
+    // a convex polygon as a triangle fan, so the colors don't go through lyon
+    fn new_polygon_colors(&mut self, corners: &[(Point, [f32; 4])]) -> PumiceResult<()> {
+        let first_vertex = self.geometry.vertices.len();
+        let first_index = self.geometry.indices.len();
+        if first_vertex + corners.len() > u16::MAX as usize {
+            return Err(PumiceError::LyonError(
+                "Too many vertices for 16 bit indices".to_string(),
+            ));
+        }
+
+        for &(point, color) in corners {
+            let vertex = Paint::Solid(color).vertex([point.x, point.y], [0.0, 0.0, 0.0]);
+            self.geometry.vertices.push(vertex);
+        }
+        let first_vertex = first_vertex as u16;
+        for i in 1..corners.len() as u16 - 1 {
+            self.geometry.indices.extend_from_slice(&[
+                first_vertex,
+                first_vertex + i,
+                first_vertex + i + 1,
+            ]);
+        }
+
+        self.finish_shape(first_index, Style::Fill)
+    }
+
+    fn renderer(&mut self) -> PumiceResult<Renderer> {
+        let renderer = Renderer::new(
             self.device.clone(),
+            self.queue.clone(),
             self.surface.clone(),
-            caps.min_image_count,
-            format,
-            dimensions,
-            1,
-            caps.supported_usage_flags,
-            &self.queue,
-            SurfaceTransform::Identity,
-            alpha,
-            PresentMode::Fifo,
-            true,
-            None,
-        )
-        .expect("failed to create swapchain");
-
-        let mut previous_frame_end =
-            Box::new(vulkano::sync::now(self.device.clone())) as Box<dyn GpuFuture>;
-
-        let render_pass = Arc::new(
-            vulkano::single_pass_renderpass!(self.device.clone(),
-            attachments: {
-                color: {
-                    load: Clear,
-                    store: Store,
-                    format: swapchain.format(),
-                    samples: 1,
-                }
-            },
-            pass: {
-                color: [color],
-                depth_stencil: {}
-            }
-            )
-            .unwrap(),
-        );
+            &self.shaders,
+            self.requested_present_mode,
+            self.samples,
+        )?;
+        self.active_present_mode = renderer.present_mode();
+        Ok(renderer)
+    }
 
-        let mut framebuffers =
-            window_size_dependent_setup(&images, render_pass.clone(), &mut self.dynamic_state);
-
-        let graphics_pipeline = Arc::new(
-            GraphicsPipeline::start()
-                .vertex_input_single_buffer::<Vertex>()
-                .vertex_shader(self.vertex_shader.main_entry_point(), ())
-                .viewports_dynamic_scissors_irrelevant(1)
-                .fragment_shader(self.fragment_shader.main_entry_point(), ())
-                .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
-                .build(self.device.clone())
-                .unwrap(),
-        );
+    // The physical device is still there after a logical device is lost, so the new device is
+    // created on the same one. Anything built on the old device has to be rebuilt as well.
+    fn recreate_device(&mut self) -> PumiceResult<()> {
+        let physical = PhysicalDevice::from_index(&self.instance, self.device_info.index)
+            .ok_or(PumiceError::NoSuitableDevice)?;
+        let queue_family = device::graphics_queue_family(physical, &self.surface)
+            .ok_or(PumiceError::NoSuitableDevice)?;
+        let (device, queue) = device::create_device(physical, queue_family)?;
+
+        self.shaders = Shaders::load(device.clone())?;
+        self.device = device;
+        self.queue = queue;
+        self.device_recreated = true;
+        Ok(())
+    }
 
-        let uniform_buffer =
-            CpuBufferPool::<vs::ty::Data>::new(self.device.clone(), BufferUsage::all());
+    fn recreate_surface(&mut self) -> PumiceResult<()> {
+        let surface = self
+            .window_builder
+            .clone()
+            .build_vk_surface(&self.events_loop, self.instance.clone())?;
+        if !surface.is_supported(self.queue.family()).unwrap_or(false) {
+            return Err(PumiceError::NoSuitableDevice);
+        }
 
-        loop {
-            if recreate_swapchain {
-                let (window_size, hidpi_factor) = {
-                    let surface = self.surface.window();
-                    (surface.get_inner_size(), surface.get_hidpi_factor())
-                };
+        fullscreen::apply(
+            surface.window(),
+            &self.fullscreen_mode,
+            self.windowed_geometry,
+        );
+        self.surface = surface;
+        self.window_mode_changed = true;
+        Ok(())
+    }
 
-                if let Some(dimensions) = window_size {
-                    let dimensions: (u32, u32) = dimensions.to_physical(hidpi_factor).into();
-                    let dimensions = [dimensions.0, dimensions.1];
-
-                    let (new_swapchain, new_images) =
-                        match swapchain.recreate_with_dimension(dimensions) {
-                            Ok(r) => r,
-                            Err(SwapchainCreationError::UnsupportedDimensions) => continue,
-                            Err(err) => panic!("Error recreating swapchain: {:?}", err),
-                        };
-
-                    swapchain = new_swapchain;
-                    framebuffers = window_size_dependent_setup(
-                        &new_images,
-                        render_pass.clone(),
-                        &mut self.dynamic_state,
-                    );
-
-                    recreate_swapchain = false;
-                }
+    fn recover(&mut self, mut renderer: Renderer, err: PumiceError) -> PumiceResult<Renderer> {
+        if let PumiceError::DeviceLost = err {
+            renderer.forget_in_flight_frame();
+        }
+        // the old swapchain has to go before its surface or device is replaced
+        drop(renderer);
+
+        let device_lost = match err {
+            PumiceError::DeviceLost => {
+                self.recreate_device()?;
+                true
             }
+            PumiceError::SurfaceLost => {
+                self.recreate_surface()?;
+                false
+            }
+            err => return Err(err),
+        };
 
-            let uniform_buffer_subbuffer = {
-                let uniform_data = vs::ty::Data {
-                    scale: [1.0 / self.screen_maxes[0], 1.0 / self.screen_maxes[1]],
-                };
+        let renderer = self.renderer()?;
+        if device_lost {
+            // taken out while it runs so it can borrow the context
+            if let Some(mut hook) = self.device_recreated_hook.take() {
+                hook(self);
+                self.device_recreated_hook = Some(hook);
+            }
+        }
+        Ok(renderer)
+    }
 
-                uniform_buffer.next(uniform_data).unwrap()
-            };
+    fn render_frame<D>(
+        &mut self,
+        renderer: &mut Renderer,
+        data: &mut D,
+        update: &dyn Fn(&mut GraphicsContext, &mut D) -> PumiceResult<()>,
+    ) -> PumiceResult<FrameResult> {
+        if self.window_mode_changed {
+            self.window_mode_changed = false;
+            self.screen_size_changed = true;
+            renderer.recreate_swapchain = true;
+        }
 
-            let set = Arc::new(
-                PersistentDescriptorSet::start(graphics_pipeline.clone(), 0)
-                    .add_buffer(uniform_buffer_subbuffer)
-                    .unwrap()
-                    .build()
-                    .unwrap(),
-            );
+        if self.present_mode_changed {
+            self.present_mode_changed = false;
+            renderer.set_present_mode(self.requested_present_mode)?;
+            self.active_present_mode = renderer.present_mode();
+        }
 
-            let (image_num, acquire_future) =
-                match vulkano::swapchain::acquire_next_image(swapchain.clone(), None) {
-                    Ok(result) => result,
-                    Err(AcquireError::OutOfDate) => {
-                        recreate_swapchain = true;
-                        continue;
-                    }
-                    Err(err) => panic!("error acquiring next image {:?}", err),
-                };
+        let frame = match renderer.acquire()? {
+            Some(frame) => frame,
+            None => return Ok(FrameResult::Skipped),
+        };
 
-            if let Err(e) = update(&mut self, data) {
-                eprintln!("Error updating: {:?}", e);
+        self.window_size = window_size(self.surface.window());
+        self.stats.begin_frame();
+        let frame_number = self.frame;
+        self.replay_delta_time = self
+            .replay
+            .as_mut()
+            .and_then(|replay| replay.delta_time_for_frame(frame_number));
+        let delta_time = self.delta_time();
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(e) = recorder.record_delta_time(self.frame, delta_time) {
+                if self.errors.report(ErrorOrigin::Recording, &e) == ErrorAction::Stop {
+                    return Ok(FrameResult::Stopped(e));
+                }
             }
+        }
+        let update_start = Instant::now();
+        if let Err(e) = update(self, data) {
+            if self.errors.report(ErrorOrigin::Update, &e) == ErrorAction::Stop {
+                return Ok(FrameResult::Stopped(e));
+            }
+        }
+        self.stats.record_update(update_start.elapsed());
+        self.frame += 1;
+        self.device_recreated = false;
+
+        // counted before the overlay so it doesn't show up in its own numbers
+        let vertices = self.geometry.vertices.len();
+        let indices = self.geometry.indices.len();
+        if self.stats_overlay {
+            stats::draw_overlay(self)?;
+        }
 
-            let command_buffer = {
-                let vertex_buffer = CpuAccessibleBuffer::from_iter(
-                    self.device.clone(),
-                    BufferUsage::all(),
-                    self.geometry.vertices.iter().cloned(),
-                )
-                .unwrap();
-                let index_buffer = CpuAccessibleBuffer::from_iter(
-                    self.device.clone(),
-                    BufferUsage::all(),
-                    self.geometry.indices.iter().cloned(),
-                )
-                .unwrap();
-
-                let clear_values = vec![clear_color.into()];
-
-                AutoCommandBufferBuilder::primary_one_time_submit(
-                    self.device.clone(),
-                    self.queue.family(),
-                )
-                .unwrap()
-                .begin_render_pass(framebuffers[image_num].clone(), false, clear_values)
-                .unwrap()
-                .draw_indexed(
-                    graphics_pipeline.clone(),
-                    &self.dynamic_state,
-                    vertex_buffer.clone(),
-                    index_buffer.clone(),
-                    set.clone(),
-                    (),
-                )
-                .unwrap()
-                .end_render_pass()
-                .unwrap()
-                .build()
-                .unwrap()
-            };
-
-            let future = previous_frame_end
-                .join(acquire_future)
-                .then_execute(self.queue.clone(), command_buffer)
-                .unwrap()
-                .then_swapchain_present(self.queue.clone(), swapchain.clone(), image_num)
-                .then_signal_fence_and_flush();
-
-            match future {
-                Ok(future) => {
-                    previous_frame_end = Box::new(future) as Box<_>;
-                }
-                Err(FlushError::OutOfDate) => {
-                    recreate_swapchain = true;
-                    previous_frame_end =
-                        Box::new(vulkano::sync::now(self.device.clone())) as Box<_>;
+        let scale = [1.0 / self.screen_maxes[0], 1.0 / self.screen_maxes[1]];
+        let submit_cpu_start = Instant::now();
+        let result = renderer.draw(
+            frame,
+            &self.geometry,
+            &self.instance_batches,
+            scale,
+            self.clear_color,
+        );
+        self.geometry.vertices.clear();
+        self.geometry.indices.clear();
+        self.instance_batches.clear();
+
+        let draw_calls = result?;
+        self.stats
+            .record_submit(submit_cpu_start.elapsed(), vertices, indices, draw_calls);
+        Ok(FrameResult::Drawn)
+    }
+
+    // `run` used to take the clear color as its last argument, this keeps that working while
+    // callers move over to `ContextBuilder::clear_color`
+    #[deprecated(note = "set `ContextBuilder::clear_color` or `clear_color` and call `run`")]
+    pub fn run_with_clear_color<D>(
+        mut self,
+        data: &mut D,
+        update: &dyn Fn(&mut GraphicsContext, &mut D) -> PumiceResult<()>,
+        handle_event: &dyn Fn(&Event, &mut D) -> PumiceResult<()>,
+        clear_color: [f32; 4],
+    ) -> PumiceResult<()> {
+        self.clear_color = clear_color;
+        self.run(data, update, handle_event)
+    }
+
+    pub fn run<D>(
+        mut self,
+        data: &mut D,
+        update: &dyn Fn(&mut GraphicsContext, &mut D) -> PumiceResult<()>,
+        handle_event: &dyn Fn(&Event, &mut D) -> PumiceResult<()>,
+    ) -> PumiceResult<()> {
+        let mut renderer = self.renderer()?;
+        // recoveries since the last frame that made it to the screen
+        let mut recoveries = 0;
+
+        loop {
+            match self.render_frame(&mut renderer, data, update) {
+                Ok(FrameResult::Skipped) => {}
+                Ok(FrameResult::Drawn) => recoveries = 0,
+                Ok(FrameResult::Stopped(e)) => return Err(e),
+                Err(e @ PumiceError::DeviceLost) | Err(e @ PumiceError::SurfaceLost) => {
+                    recoveries += 1;
+                    if recoveries > MAX_RECOVERIES {
+                        return Err(e);
+                    }
+                    log::warn!("{}, recreating the renderer", e);
+                    renderer = self.recover(renderer, e)?;
                 }
-                Err(e) => {
-                    println!("{:?}", e);
-                    previous_frame_end =
-                        Box::new(vulkano::sync::now(self.device.clone())) as Box<_>;
+                Err(e @ PumiceError::Render(_)) => {
+                    if self.errors.report(ErrorOrigin::Render, &e) == ErrorAction::Stop {
+                        return Err(e);
+                    }
                 }
+                Err(e) => return Err(e),
             }
-            self.geometry.vertices.clear();
-            self.geometry.indices.clear();
+
+            self.frame_limiter.wait();
 
             let mut close = false;
             let mut screen_size_changed = false;
+            let replaying = self.replay.is_some();
+
+            let mut events = Vec::new();
             self.events_loop.poll_events(|event| {
-                if let Err(e) = handle_event(&event, data) {
-                    eprintln!("Error handling events: {:?}", e);
-                };
+                if !replaying {
+                    events.push(Event::Winit(event));
+                    return;
+                }
+
                 match event {
                     winit::Event::WindowEvent {
                         event: winit::WindowEvent::CloseRequested,
@@ -516,14 +911,95 @@ impl GraphicsContext {
                         event: winit::WindowEvent::Resized(_),
                         ..
                     } => {
-                        recreate_swapchain = true;
+                        renderer.recreate_swapchain = true;
                         screen_size_changed = true;
                     }
                     _ => {}
                 };
             });
 
+            if replaying {
+                self.gamepads.discard_events();
+            } else {
+                let gamepad_events = self.gamepads.poll_events();
+                events.extend(gamepad_events.into_iter().map(Event::Gamepad));
+            }
+
+            if let Some(replay) = &mut self.replay {
+                for event in replay.events_for_frame(self.frame) {
+                    // live pads aren't polled during a replay, so this is what keeps
+                    // `gamepads` in step with the recording
+                    if let Event::Gamepad(gamepad_event) = event {
+                        self.gamepads.apply(gamepad_event);
+                    }
+                    events.push(event);
+                }
+                if replay.is_finished() {
+                    close |= replay.exit_when_finished;
+                    self.replay = None;
+                }
+            }
+
+            self.text_input.clear();
+            for event in events.iter() {
+                match event {
+                    Event::Winit(winit::Event::WindowEvent {
+                        event: winit::WindowEvent::CloseRequested,
+                        ..
+                    }) => {
+                        close = true;
+                    }
+                    Event::Winit(winit::Event::WindowEvent {
+                        event: winit::WindowEvent::Resized(_),
+                        ..
+                    }) => {
+                        renderer.recreate_swapchain = true;
+                        screen_size_changed = true;
+                    }
+                    Event::Winit(winit::Event::WindowEvent {
+                        event:
+                            winit::WindowEvent::KeyboardInput {
+                                input:
+                                    winit::KeyboardInput {
+                                        state: winit::ElementState::Pressed,
+                                        virtual_keycode: Some(winit::VirtualKeyCode::Return),
+                                        modifiers,
+                                        ..
+                                    },
+                                ..
+                            },
+                        ..
+                    }) if modifiers.alt && self.alt_enter_fullscreen => {
+                        self.toggle_fullscreen();
+                    }
+                    Event::Winit(winit::Event::WindowEvent {
+                        event: winit::WindowEvent::ReceivedCharacter(c),
+                        ..
+                    }) => {
+                        self.text_input.extend(TextEvent::from_char(*c));
+                    }
+                    _ => {}
+                };
+
+                if let Some(recorder) = &mut self.recorder {
+                    if let Err(e) = recorder.record(self.frame, event) {
+                        if self.errors.report(ErrorOrigin::Recording, &e) == ErrorAction::Stop {
+                            return Err(e);
+                        }
+                    }
+                }
+
+                if let Err(e) = handle_event(event, data) {
+                    if self.errors.report(ErrorOrigin::HandleEvent, &e) == ErrorAction::Stop {
+                        return Err(e);
+                    }
+                }
+            }
+
             if close {
+                if let Some(recorder) = &mut self.recorder {
+                    recorder.flush()?;
+                }
                 return Ok(());
             }
             self.screen_size_changed = screen_size_changed;
@@ -531,36 +1007,15 @@ impl GraphicsContext {
     }
 }
 
+fn window_size(window: &Window) -> [f32; 2] {
+    window.get_inner_size().map_or([0.0, 0.0], |size| {
+        let size = size.to_physical(window.get_hidpi_factor());
+        [size.width as f32, size.height as f32]
+    })
+}
+
 impl Default for GraphicsContext {
     fn default() -> Self {
         Self::new()
     }
 }
-
-fn window_size_dependent_setup(
-    images: &[Arc<SwapchainImage<Window>>],
-    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
-    dynamic_state: &mut DynamicState,
-) -> Vec<Arc<dyn FramebufferAbstract + Send + Sync>> {
-    let dimensions = images[0].dimensions();
-
-    let viewport = Viewport {
-        origin: [0.0, 0.0],
-        dimensions: [dimensions[0] as f32, dimensions[1] as f32],
-        depth_range: 0.0..1.0,
-    };
-    dynamic_state.viewports = Some(vec![viewport]);
-
-    images
-        .iter()
-        .map(|image| {
-            Arc::new(
-                Framebuffer::start(render_pass.clone())
-                    .add(image.clone())
-                    .unwrap()
-                    .build()
-                    .unwrap(),
-            ) as Arc<dyn FramebufferAbstract + Send + Sync>
-        })
-        .collect::<Vec<_>>()
-}
@@ -0,0 +1,121 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+// sleeping is only accurate to a millisecond or two, so the end of each frame is spun out
+const SPIN_MARGIN: Duration = Duration::from_millis(2);
+
+/// Caps the frame rate on the CPU side, for when the present mode isn't doing it already.
+pub struct FrameLimiter {
+    frame_time: Option<Duration>,
+    next_frame: Instant,
+}
+
+impl FrameLimiter {
+    pub fn new(target_fps: Option<f64>) -> Self {
+        let mut limiter = FrameLimiter {
+            frame_time: None,
+            next_frame: Instant::now(),
+        };
+        limiter.set_target_fps(target_fps);
+        limiter
+    }
+
+    // None, or an fps that isn't a positive number with a frame time that fits in a Duration,
+    // turns the limiter off
+    pub fn set_target_fps(&mut self, target_fps: Option<f64>) {
+        self.frame_time = match target_fps {
+            Some(fps) if fps.is_finite() && fps > 0.0 => {
+                Duration::try_from_secs_f64(1.0 / fps).ok()
+            }
+            _ => None,
+        };
+        self.next_frame = Instant::now();
+    }
+
+    pub fn target_fps(&self) -> Option<f64> {
+        self.frame_time
+            .map(|frame_time| 1.0 / frame_time.as_secs_f64())
+    }
+
+    pub fn wait(&mut self) {
+        let frame_time = match self.frame_time {
+            Some(frame_time) => frame_time,
+            None => return,
+        };
+
+        let now = Instant::now();
+        if now < self.next_frame {
+            let remaining = self.next_frame - now;
+            if remaining > SPIN_MARGIN {
+                thread::sleep(remaining - SPIN_MARGIN);
+            }
+            while Instant::now() < self.next_frame {
+                std::hint::spin_loop();
+            }
+            self.next_frame += frame_time;
+        } else {
+            // running behind, start counting from now instead of trying to catch up
+            self.next_frame = now + frame_time;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_targets_turn_the_limiter_off() {
+        for &fps in &[
+            None,
+            Some(0.0),
+            Some(-1.0),
+            Some(f64::NAN),
+            Some(f64::INFINITY),
+            // the frame time would overflow a Duration
+            Some(1e-300),
+        ] {
+            assert_eq!(FrameLimiter::new(fps).frame_time, None, "{:?}", fps);
+        }
+
+        let limiter = FrameLimiter::new(Some(60.0));
+        assert_eq!(
+            limiter.frame_time,
+            Some(Duration::from_secs_f64(1.0 / 60.0))
+        );
+        assert!((limiter.target_fps().unwrap() - 60.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn on_time_frames_advance_by_the_frame_time() {
+        let mut limiter = FrameLimiter::new(Some(100.0));
+        let next_frame = Instant::now() + Duration::from_millis(5);
+        limiter.next_frame = next_frame;
+
+        limiter.wait();
+        assert!(Instant::now() >= next_frame);
+        // keeps to the schedule rather than counting from when wait returned
+        assert_eq!(limiter.next_frame, next_frame + Duration::from_millis(10));
+    }
+
+    #[test]
+    fn late_frames_restart_from_now() {
+        let mut limiter = FrameLimiter::new(Some(100.0));
+        let before = Instant::now();
+        limiter.next_frame = before - Duration::from_millis(50);
+
+        limiter.wait();
+        assert!(limiter.next_frame >= before + Duration::from_millis(10));
+        assert!(limiter.next_frame <= Instant::now() + Duration::from_millis(10));
+    }
+
+    #[test]
+    fn unlimited_doesnt_wait() {
+        let mut limiter = FrameLimiter::new(None);
+        let next_frame = Instant::now() + Duration::from_secs(60);
+        limiter.next_frame = next_frame;
+
+        limiter.wait();
+        assert_eq!(limiter.next_frame, next_frame);
+    }
+}
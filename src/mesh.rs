@@ -0,0 +1,282 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+use lyon::math::{point, Point};
+use lyon::path::Path;
+use lyon::tessellation::basic_shapes::{self, BorderRadii};
+use lyon::tessellation::geometry_builder::{simple_builder, SimpleBuffersBuilder};
+use lyon::tessellation::math::{Rect, Size};
+use lyon::tessellation::{FillOptions, FillTessellator, TessellationResult, VertexBuffers};
+
+use crate::error::{PumiceError, PumiceResult};
+use crate::shapes::ellipse_points;
+use crate::{GraphicsContext, Paint, Rotation, Style, Vertex};
+
+// used when there's no window size to go by, about a tenth of a pixel on a 1080p window
+pub(crate) const DEFAULT_TOLERANCE: f32 = 0.0001;
+// a `Tolerance` below this, or one that's zero, negative or NaN, is raised to it so lyon doesn't
+// produce an endless number of vertices
+const MIN_TOLERANCE: f32 = 0.000_001;
+
+// cached meshes are thrown out once there are this many, e.g. for a circle whose radius animates
+const MAX_CACHED: usize = 256;
+
+/// How closely curves are approximated. The named levels are the maximum error in pixels, so
+/// small shapes get fewer triangles and zooming in (shrinking `screen_maxes`) gets more.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub enum TessellationQuality {
+    // 1 pixel
+    Low,
+    // half a pixel
+    Medium,
+    // a tenth of a pixel
+    #[default]
+    High,
+    // a fixed tolerance in game coordinates, whatever the window size. Clamped to a small positive
+    // minimum.
+    Tolerance(f32),
+}
+
+impl TessellationQuality {
+    // `pixel_size` is the size of a pixel in game coordinates, if it's known
+    pub(crate) fn tolerance(self, pixel_size: Option<f32>) -> f32 {
+        let pixels = match self {
+            TessellationQuality::Low => 1.0,
+            TessellationQuality::Medium => 0.5,
+            TessellationQuality::High => 0.1,
+            // `max` turns NaN into the minimum as well
+            TessellationQuality::Tolerance(tolerance) => return tolerance.max(MIN_TOLERANCE),
+        };
+        // a tiny window or a big zoom can't go below the minimum either
+        pixel_size.map_or(DEFAULT_TOLERANCE, |pixel_size| {
+            (pixel_size * pixels).max(MIN_TOLERANCE)
+        })
+    }
+}
+
+/// A shape that's tessellated once, centered on the origin, and can then be drawn any number of
+/// times at different positions, scales and paints without going through lyon again.
+#[derive(Clone, Debug)]
+pub struct Mesh {
+    geometry: VertexBuffers<Point, u16>,
+}
+
+impl Mesh {
+    pub fn circle(radius: f32) -> PumiceResult<Self> {
+        Self::circle_with_tolerance(radius, DEFAULT_TOLERANCE)
+    }
+
+    // see GraphicsContext::tolerance
+    pub fn circle_with_tolerance(radius: f32, tolerance: f32) -> PumiceResult<Self> {
+        Self::tessellate(|builder| {
+            basic_shapes::fill_circle(
+                point(0.0, 0.0),
+                radius,
+                &FillOptions::tolerance(tolerance),
+                builder,
+            )
+        })
+    }
+
+    pub fn ellipse(radii: [f32; 2]) -> PumiceResult<Self> {
+        Self::ellipse_with_tolerance(radii, DEFAULT_TOLERANCE)
+    }
+
+    pub fn ellipse_with_tolerance(radii: [f32; 2], tolerance: f32) -> PumiceResult<Self> {
+        let points = ellipse_points(point(0.0, 0.0), radii, tolerance);
+        if points.is_empty() {
+            return Ok(Mesh {
+                geometry: VertexBuffers::new(),
+            });
+        }
+        let mut path_builder = Path::builder();
+        path_builder.move_to(points[0]);
+        for &p in &points[1..] {
+            path_builder.line_to(p);
+        }
+        path_builder.close();
+        let path = path_builder.build();
+
+        Self::tessellate(|builder| {
+            FillTessellator::new().tessellate_path(
+                &path,
+                &FillOptions::tolerance(tolerance),
+                builder,
+            )
+        })
+    }
+
+    pub fn rectangle(sides: impl Into<Size>) -> PumiceResult<Self> {
+        let sides = sides.into();
+        let rect = Rect::new(point(-sides.width / 2.0, -sides.height / 2.0), sides);
+        Self::tessellate(|builder| {
+            basic_shapes::fill_rectangle(&rect, &FillOptions::non_zero(), builder)
+        })
+    }
+
+    pub fn rounded_rectangle(sides: impl Into<Size>, radius: f32) -> PumiceResult<Self> {
+        Self::rounded_rectangle_with_tolerance(sides, radius, DEFAULT_TOLERANCE)
+    }
+
+    pub fn rounded_rectangle_with_tolerance(
+        sides: impl Into<Size>,
+        radius: f32,
+        tolerance: f32,
+    ) -> PumiceResult<Self> {
+        let sides = sides.into();
+        let rect = Rect::new(point(-sides.width / 2.0, -sides.height / 2.0), sides);
+        Self::tessellate(|builder| {
+            basic_shapes::fill_rounded_rectangle(
+                &rect,
+                &BorderRadii::new_all_same(radius),
+                &FillOptions::tolerance(tolerance),
+                builder,
+            )
+        })
+    }
+
+    fn tessellate(
+        f: impl FnOnce(&mut SimpleBuffersBuilder) -> TessellationResult,
+    ) -> PumiceResult<Self> {
+        let mut geometry = VertexBuffers::new();
+        f(&mut simple_builder(&mut geometry))?;
+        Ok(Mesh { geometry })
+    }
+
+    pub(crate) fn geometry(&self) -> &VertexBuffers<Point, u16> {
+        &self.geometry
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.geometry.vertices.len()
+    }
+
+    pub fn index_count(&self) -> usize {
+        self.geometry.indices.len()
+    }
+
+    /// Appends the mesh to `geometry`, moved to `pos` and scaled by `scale`. `GraphicsContext`
+    /// does this in `draw_mesh`, it's public for drawing into buffers of your own.
+    pub fn append_to(
+        &self,
+        geometry: &mut VertexBuffers<Vertex, u16>,
+        pos: [f32; 2],
+        scale: f32,
+        paint: Paint,
+        rot: Option<Rotation>,
+    ) -> PumiceResult<()> {
+        let first_vertex = geometry.vertices.len();
+        if first_vertex + self.geometry.vertices.len() > u16::MAX as usize {
+            return Err(PumiceError::LyonError(
+                "Too many vertices for 16 bit indices".to_string(),
+            ));
+        }
+
+        let rot = match rot {
+            Some(rot) => [rot.point[0], rot.point[1], rot.degrees],
+            None => [0.0, 0.0, 0.0],
+        };
+        geometry.vertices.extend(
+            self.geometry
+                .vertices
+                .iter()
+                .map(|p| paint.vertex([pos[0] + p.x * scale, pos[1] + p.y * scale], rot)),
+        );
+
+        let first_vertex = first_vertex as u16;
+        geometry
+            .indices
+            .extend(self.geometry.indices.iter().map(|i| i + first_vertex));
+        Ok(())
+    }
+}
+
+// circles keyed by the bits of their radius and tolerance, so `new_circle` only tessellates each
+// size once
+pub(crate) struct MeshCache {
+    circles: HashMap<(u32, u32), Mesh>,
+}
+
+impl MeshCache {
+    pub(crate) fn new() -> Self {
+        MeshCache {
+            circles: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn circle(&mut self, radius: f32, tolerance: f32) -> PumiceResult<&Mesh> {
+        let key = (radius.to_bits(), tolerance.to_bits());
+        if self.circles.len() >= MAX_CACHED && !self.circles.contains_key(&key) {
+            self.circles.clear();
+        }
+
+        let mesh = match self.circles.entry(key) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(Mesh::circle_with_tolerance(radius, tolerance)?),
+        };
+        Ok(mesh)
+    }
+}
+
+impl GraphicsContext {
+    pub fn draw_mesh(
+        &mut self,
+        mesh: &Mesh,
+        pos: impl Into<Point>,
+        paint: impl Into<Paint>,
+    ) -> PumiceResult<()> {
+        self.draw_mesh_full(mesh, pos, 1.0, paint, None)
+    }
+
+    pub fn draw_mesh_full(
+        &mut self,
+        mesh: &Mesh,
+        pos: impl Into<Point>,
+        scale: f32,
+        paint: impl Into<Paint>,
+        rot: Option<Rotation>,
+    ) -> PumiceResult<()> {
+        let pos = pos.into();
+        let first_index = self.geometry.indices.len();
+        mesh.append_to(&mut self.geometry, [pos.x, pos.y], scale, paint.into(), rot)?;
+        self.finish_shape(first_index, Style::Fill)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tolerance_follows_the_pixel_size() {
+        assert_eq!(TessellationQuality::Low.tolerance(Some(0.01)), 0.01);
+        assert_eq!(TessellationQuality::Medium.tolerance(Some(0.01)), 0.005);
+        assert_eq!(TessellationQuality::High.tolerance(None), DEFAULT_TOLERANCE);
+        assert_eq!(
+            TessellationQuality::Tolerance(0.02).tolerance(Some(0.01)),
+            0.02
+        );
+    }
+
+    #[test]
+    fn tolerance_is_clamped() {
+        // zoomed in far enough that a tenth of a pixel is below the minimum
+        assert_eq!(
+            TessellationQuality::High.tolerance(Some(1e-9)),
+            MIN_TOLERANCE
+        );
+        assert_eq!(TessellationQuality::Low.tolerance(Some(0.0)), MIN_TOLERANCE);
+        assert_eq!(
+            TessellationQuality::Medium.tolerance(Some(f32::NAN)),
+            MIN_TOLERANCE
+        );
+        assert_eq!(
+            TessellationQuality::Tolerance(-1.0).tolerance(None),
+            MIN_TOLERANCE
+        );
+        assert_eq!(
+            TessellationQuality::Tolerance(f32::NAN).tolerance(Some(0.01)),
+            MIN_TOLERANCE
+        );
+    }
+}
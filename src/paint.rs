@@ -0,0 +1,102 @@
+use crate::Vertex;
+
+// values for `Vertex::gradient_type`, the fragment shader checks these
+const SOLID: f32 = 0.0;
+const LINEAR: f32 = 1.0;
+const RADIAL: f32 = 2.0;
+
+/// What a shape is filled with. Every shape function takes `impl Into<Paint>`, so a plain
+/// `[r, g, b, a]` still works for a solid color.
+///
+/// Gradient points are in the same coordinates as the shape, and rotate along with it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Paint {
+    Solid([f32; 4]),
+    // blends from start_color at `start` to end_color at `end`, flat past either end
+    Linear {
+        start: [f32; 2],
+        end: [f32; 2],
+        start_color: [f32; 4],
+        end_color: [f32; 4],
+    },
+    // blends from inner_color at `center` to outer_color at `radius` and beyond
+    Radial {
+        center: [f32; 2],
+        radius: f32,
+        inner_color: [f32; 4],
+        outer_color: [f32; 4],
+    },
+}
+
+impl Paint {
+    pub fn linear(
+        start: [f32; 2],
+        end: [f32; 2],
+        start_color: [f32; 4],
+        end_color: [f32; 4],
+    ) -> Self {
+        Paint::Linear {
+            start,
+            end,
+            start_color,
+            end_color,
+        }
+    }
+
+    pub fn radial(
+        center: [f32; 2],
+        radius: f32,
+        inner_color: [f32; 4],
+        outer_color: [f32; 4],
+    ) -> Self {
+        Paint::Radial {
+            center,
+            radius,
+            inner_color,
+            outer_color,
+        }
+    }
+
+    pub(crate) fn vertex(&self, position: [f32; 2], rot: [f32; 3]) -> Vertex {
+        let (color, gradient_color, gradient, gradient_type) = match *self {
+            Paint::Solid(color) => (color, color, [0.0; 4], SOLID),
+            Paint::Linear {
+                start,
+                end,
+                start_color,
+                end_color,
+            } => (
+                start_color,
+                end_color,
+                [start[0], start[1], end[0], end[1]],
+                LINEAR,
+            ),
+            Paint::Radial {
+                center,
+                radius,
+                inner_color,
+                outer_color,
+            } => (
+                inner_color,
+                outer_color,
+                [center[0], center[1], radius, 0.0],
+                RADIAL,
+            ),
+        };
+
+        Vertex {
+            position,
+            color,
+            rot,
+            gradient_color,
+            gradient,
+            gradient_type,
+        }
+    }
+}
+
+impl From<[f32; 4]> for Paint {
+    fn from(color: [f32; 4]) -> Self {
+        Paint::Solid(color)
+    }
+}
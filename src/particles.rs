@@ -0,0 +1,380 @@
+use crate::error::PumiceResult;
+use crate::instancing::MeshInstance;
+use crate::{GraphicsContext, Mesh};
+
+// a tiny xorshift generator, so emitters don't need rand and replays spawn the same particles
+struct Rng(u32);
+
+impl Rng {
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        (self.0 >> 8) as f32 / (1 << 24) as f32
+    }
+
+    fn range(&mut self, range: [f32; 2]) -> f32 {
+        range[0] + (range[1] - range[0]) * self.next()
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// What each particle is drawn as. Sizes are scaled by the emitter's size over lifetime, so a
+/// circle's radius and a rectangle's sides are its size at 1.0.
+///
+/// There's no sprite support since the renderer doesn't draw textures yet; `Mesh` covers other
+/// shapes.
+#[derive(Clone, Debug)]
+pub enum ParticleShape {
+    Circle,
+    Rectangle([f32; 2]),
+    Mesh(Mesh),
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Particle {
+    position: [f32; 2],
+    velocity: [f32; 2],
+    age: f32,
+    lifetime: f32,
+}
+
+/// Spawns, moves and draws particles on the CPU. Call `update` with the frame time every frame,
+/// then `draw`, which draws every particle with a single instanced draw call.
+///
+/// Angles are in degrees, clockwise from the positive x axis like the shape functions.
+pub struct ParticleEmitter {
+    // where new particles spawn, move it around for trails
+    pub position: [f32; 2],
+    // turn off to let the live particles die out, e.g. before removing the emitter
+    pub emitting: bool,
+    rate: f32,
+    lifetime: [f32; 2],
+    speed: [f32; 2],
+    angle: f32,
+    spread: f32,
+    gravity: [f32; 2],
+    start_color: [f32; 4],
+    end_color: [f32; 4],
+    start_size: f32,
+    end_size: f32,
+    shape: ParticleShape,
+    align_to_velocity: bool,
+    max_particles: usize,
+    particles: Vec<Particle>,
+    // fractional particles left over from previous updates
+    to_spawn: f32,
+    rng: Rng,
+    // built from `shape` on the first draw, with the tolerance circles were built with
+    mesh: Option<(f32, Mesh)>,
+}
+
+impl ParticleEmitter {
+    pub fn new(position: [f32; 2]) -> Self {
+        ParticleEmitter {
+            position,
+            emitting: true,
+            rate: 50.0,
+            lifetime: [1.0, 1.0],
+            speed: [0.1, 0.2],
+            angle: 270.0,
+            spread: 360.0,
+            gravity: [0.0, 0.0],
+            start_color: [1.0, 1.0, 1.0, 1.0],
+            end_color: [1.0, 1.0, 1.0, 0.0],
+            start_size: 0.01,
+            end_size: 0.01,
+            shape: ParticleShape::Circle,
+            align_to_velocity: false,
+            max_particles: 10_000,
+            particles: Vec::new(),
+            to_spawn: 0.0,
+            rng: Rng(0x9e37_79b9),
+            mesh: None,
+        }
+    }
+
+    // particles per second while `emitting`, 0 for an emitter that only does bursts. A negative or
+    // non-finite rate is rejected and turns into 0.
+    pub fn rate(mut self, rate: f32) -> Self {
+        self.rate = if rate.is_finite() && rate >= 0.0 {
+            rate
+        } else {
+            log::warn!("Invalid particle rate {}, using 0", rate);
+            0.0
+        };
+        self
+    }
+
+    // seconds, each particle picks a lifetime between min and max. Particles that pick 0 or less
+    // aren't spawned at all.
+    pub fn lifetime(mut self, min: f32, max: f32) -> Self {
+        self.lifetime = [min, max];
+        self
+    }
+
+    // game units per second, picked between min and max
+    pub fn speed(mut self, min: f32, max: f32) -> Self {
+        self.speed = [min, max];
+        self
+    }
+
+    // particles head off within `spread` degrees of `angle`, so 360 goes every direction
+    pub fn direction(mut self, angle: f32, spread: f32) -> Self {
+        self.angle = angle;
+        self.spread = spread;
+        self
+    }
+
+    // game units per second per second, positive y is down
+    pub fn gravity(mut self, gravity: [f32; 2]) -> Self {
+        self.gravity = gravity;
+        self
+    }
+
+    // blends from start to end over each particle's lifetime
+    pub fn color(mut self, start: [f32; 4], end: [f32; 4]) -> Self {
+        self.start_color = start;
+        self.end_color = end;
+        self
+    }
+
+    pub fn size(mut self, start: f32, end: f32) -> Self {
+        self.start_size = start;
+        self.end_size = end;
+        self
+    }
+
+    pub fn shape(mut self, shape: ParticleShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    // turns each particle to face the way it's moving, for streaks like rain and sparks
+    pub fn align_to_velocity(mut self, align: bool) -> Self {
+        self.align_to_velocity = align;
+        self
+    }
+
+    // new particles aren't spawned past this many
+    pub fn max_particles(mut self, max_particles: usize) -> Self {
+        self.max_particles = max_particles;
+        self
+    }
+
+    // for emitters that should look different from each other
+    pub fn seed(mut self, seed: u32) -> Self {
+        // xorshift gets stuck on 0
+        self.rng = Rng(seed.max(1));
+        self
+    }
+
+    pub fn particle_count(&self) -> usize {
+        self.particles.len()
+    }
+
+    // true once the emitter is off and every particle has died
+    pub fn is_finished(&self) -> bool {
+        !self.emitting && self.particles.is_empty()
+    }
+
+    // spawns `count` particles at once, for explosions and the like
+    pub fn burst(&mut self, count: usize) {
+        for _ in 0..count {
+            self.spawn();
+        }
+    }
+
+    fn spawn(&mut self) {
+        if self.particles.len() >= self.max_particles {
+            return;
+        }
+
+        let angle = (self.angle + self.spread * (self.rng.next() - 0.5)).to_radians();
+        let speed = self.rng.range(self.speed);
+        let lifetime = self.rng.range(self.lifetime);
+        // they'd never be seen, and `draw` divides by the lifetime
+        if lifetime.is_nan() || lifetime <= 0.0 {
+            return;
+        }
+        self.particles.push(Particle {
+            position: self.position,
+            velocity: [angle.cos() * speed, angle.sin() * speed],
+            age: 0.0,
+            lifetime,
+        });
+    }
+
+    // `dt` is in seconds, usually `ctx.delta_time()`
+    pub fn update(&mut self, dt: f32) {
+        let gravity = self.gravity;
+        for particle in self.particles.iter_mut() {
+            particle.velocity[0] += gravity[0] * dt;
+            particle.velocity[1] += gravity[1] * dt;
+            particle.position[0] += particle.velocity[0] * dt;
+            particle.position[1] += particle.velocity[1] * dt;
+            particle.age += dt;
+        }
+        self.particles
+            .retain(|particle| particle.age < particle.lifetime);
+
+        if self.emitting {
+            self.to_spawn += self.rate * dt;
+            let whole = self.to_spawn.floor();
+            // a huge `dt` can't spawn more than the emitter holds, and NaN spawns nothing
+            let count = (whole as usize).min(self.max_particles);
+            self.to_spawn = if whole.is_finite() {
+                self.to_spawn - whole
+            } else {
+                0.0
+            };
+            for _ in 0..count {
+                self.spawn();
+            }
+        }
+    }
+
+    fn mesh(&mut self, tolerance: f32) -> PumiceResult<&Mesh> {
+        let tolerance = match self.shape {
+            // built at radius 1, so the tolerance has to shrink to match the largest particle
+            ParticleShape::Circle => {
+                tolerance / self.start_size.max(self.end_size).max(f32::EPSILON)
+            }
+            _ => 0.0,
+        };
+        let stale = match self.mesh {
+            Some((built_with, _)) => built_with != tolerance,
+            None => true,
+        };
+        if stale {
+            let mesh = match &self.shape {
+                ParticleShape::Circle => Mesh::circle_with_tolerance(1.0, tolerance)?,
+                ParticleShape::Rectangle(_) => Mesh::rectangle([1.0, 1.0])?,
+                ParticleShape::Mesh(mesh) => mesh.clone(),
+            };
+            self.mesh = Some((tolerance, mesh));
+        }
+        Ok(&self.mesh.as_ref().unwrap().1)
+    }
+
+    pub fn draw(&mut self, ctx: &mut GraphicsContext) -> PumiceResult<()> {
+        if self.particles.is_empty() {
+            return Ok(());
+        }
+
+        let sides = match self.shape {
+            ParticleShape::Rectangle(sides) => sides,
+            _ => [1.0, 1.0],
+        };
+        let instances = self
+            .particles
+            .iter()
+            .map(|particle| {
+                let t = particle.age / particle.lifetime;
+                let size = lerp(self.start_size, self.end_size, t);
+                let mut color = [0.0; 4];
+                for (i, c) in color.iter_mut().enumerate() {
+                    *c = lerp(self.start_color[i], self.end_color[i], t);
+                }
+                let rotation = if self.align_to_velocity {
+                    particle.velocity[1]
+                        .atan2(particle.velocity[0])
+                        .to_degrees()
+                } else {
+                    0.0
+                };
+
+                MeshInstance {
+                    position: particle.position,
+                    scale: [sides[0] * size, sides[1] * size],
+                    rotation,
+                    color,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mesh = self.mesh(ctx.tolerance())?;
+        ctx.draw_instanced(mesh, &instances)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bursts_stop_at_max_particles() {
+        let mut emitter = ParticleEmitter::new([0.0, 0.0]).rate(0.0).max_particles(10);
+        emitter.burst(4);
+        assert_eq!(emitter.particle_count(), 4);
+        emitter.burst(100);
+        assert_eq!(emitter.particle_count(), 10);
+    }
+
+    #[test]
+    fn particles_expire_after_their_lifetime() {
+        let mut emitter = ParticleEmitter::new([0.0, 0.0])
+            .rate(0.0)
+            .lifetime(0.5, 1.0);
+        emitter.burst(20);
+        emitter.update(0.4);
+        assert_eq!(emitter.particle_count(), 20);
+        emitter.update(0.3);
+        assert!(emitter.particle_count() < 20);
+        emitter.update(0.31);
+        assert_eq!(emitter.particle_count(), 0);
+
+        // not finished until it stops emitting
+        assert!(!emitter.is_finished());
+        emitter.emitting = false;
+        assert!(emitter.is_finished());
+    }
+
+    #[test]
+    fn fractional_spawns_carry_over() {
+        let mut emitter = ParticleEmitter::new([0.0, 0.0])
+            .rate(10.0)
+            .lifetime(100.0, 100.0);
+        // a third of a particle each update
+        for _ in 0..8 {
+            emitter.update(1.0 / 30.0);
+        }
+        assert_eq!(emitter.particle_count(), 2);
+        emitter.update(1.0 / 30.0);
+        assert_eq!(emitter.particle_count(), 3);
+    }
+
+    #[test]
+    fn spawns_per_update_are_capped() {
+        let mut emitter = ParticleEmitter::new([0.0, 0.0])
+            .rate(1e30)
+            .max_particles(100);
+        // would otherwise loop forever
+        emitter.update(f32::INFINITY);
+        assert_eq!(emitter.particle_count(), 100);
+        assert_eq!(emitter.to_spawn, 0.0);
+
+        emitter.update(f32::NAN);
+        assert_eq!(emitter.to_spawn, 0.0);
+        // NaN doesn't stick around and stop later spawns
+        emitter.update(0.0);
+        assert_eq!(emitter.to_spawn, 0.0);
+        emitter.emitting = false;
+        emitter.update(1.0);
+        assert_eq!(emitter.particle_count(), 0);
+    }
+
+    #[test]
+    fn invalid_rates_are_rejected() {
+        for &rate in &[-1.0, f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            let mut emitter = ParticleEmitter::new([0.0, 0.0]).rate(rate);
+            assert_eq!(emitter.rate, 0.0);
+            emitter.update(1.0);
+            assert_eq!(emitter.particle_count(), 0);
+        }
+        assert_eq!(ParticleEmitter::new([0.0, 0.0]).rate(5.0).rate, 5.0);
+    }
+}
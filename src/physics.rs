@@ -0,0 +1,520 @@
+use crate::collision::{Aabb, Circle, Contact, OrientedRect, Polygon, Shape};
+use crate::error::PumiceResult;
+use crate::spatial::SpatialHash;
+use crate::{GraphicsContext, Paint, Rotation};
+
+// overlap that's left alone so resting bodies don't jitter
+const SLOP: f32 = 0.001;
+// how much of the remaining overlap is pushed out each substep
+const CORRECTION: f32 = 0.8;
+
+/// A body's shape, centered on its position and turned by its angle.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Collider {
+    // radius
+    Circle(f32),
+    // sides
+    Rectangle([f32; 2]),
+    // a convex polygon, relative to the body's position
+    Polygon(Vec<[f32; 2]>),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BodyKind {
+    // moved by gravity and collisions
+    Dynamic,
+    // never moves, for walls and floors
+    Static,
+    // moves at its velocity but isn't pushed by anything, for moving platforms
+    Kinematic,
+}
+
+/// Something in a `World`. Collisions push bodies apart and change their velocity, but not their
+/// rotation; `angular_velocity` only spins them.
+#[derive(Clone, Debug)]
+pub struct Body {
+    // the center of the collider
+    pub position: [f32; 2],
+    // game units per second
+    pub velocity: [f32; 2],
+    // degrees, clockwise like `Rotation`
+    pub angle: f32,
+    // degrees per second
+    pub angular_velocity: f32,
+    kind: BodyKind,
+    collider: Collider,
+    inverse_mass: f32,
+    restitution: f32,
+    friction: f32,
+}
+
+impl Body {
+    pub fn new(kind: BodyKind, position: [f32; 2], collider: Collider) -> Self {
+        Body {
+            position,
+            velocity: [0.0, 0.0],
+            angle: 0.0,
+            angular_velocity: 0.0,
+            kind,
+            collider,
+            inverse_mass: match kind {
+                BodyKind::Dynamic => 1.0,
+                _ => 0.0,
+            },
+            restitution: 0.5,
+            friction: 0.3,
+        }
+    }
+
+    pub fn dynamic(position: [f32; 2], collider: Collider) -> Self {
+        Self::new(BodyKind::Dynamic, position, collider)
+    }
+
+    pub fn fixed(position: [f32; 2], collider: Collider) -> Self {
+        Self::new(BodyKind::Static, position, collider)
+    }
+
+    pub fn kinematic(position: [f32; 2], collider: Collider) -> Self {
+        Self::new(BodyKind::Kinematic, position, collider)
+    }
+
+    // ignored for static and kinematic bodies, which act as if they weigh infinitely much
+    pub fn mass(mut self, mass: f32) -> Self {
+        if self.kind == BodyKind::Dynamic && mass > 0.0 {
+            self.inverse_mass = 1.0 / mass;
+        }
+        self
+    }
+
+    // how bouncy collisions are, from 0 (not at all) to 1 (keeps all its speed). The smaller of
+    // the two bodies' restitution is used.
+    pub fn restitution(mut self, restitution: f32) -> Self {
+        self.restitution = restitution;
+        self
+    }
+
+    pub fn friction(mut self, friction: f32) -> Self {
+        self.friction = friction;
+        self
+    }
+
+    pub fn kind(&self) -> BodyKind {
+        self.kind
+    }
+
+    pub fn collider(&self) -> &Collider {
+        &self.collider
+    }
+
+    // the collider where the body is now, for collision queries of your own
+    pub fn shape(&self) -> Shape {
+        let (sin, cos) = self.angle.to_radians().sin_cos();
+        match &self.collider {
+            Collider::Circle(radius) => Circle::new(self.position, *radius).into(),
+            Collider::Rectangle(sides) => OrientedRect::new(
+                [
+                    self.position[0] - sides[0] / 2.0,
+                    self.position[1] - sides[1] / 2.0,
+                ],
+                *sides,
+                Rotation {
+                    degrees: self.angle,
+                    point: self.position,
+                },
+            )
+            .into(),
+            Collider::Polygon(points) => Polygon::new(
+                points
+                    .iter()
+                    .map(|p| {
+                        [
+                            self.position[0] + cos * p[0] - sin * p[1],
+                            self.position[1] + sin * p[0] + cos * p[1],
+                        ]
+                    })
+                    .collect(),
+            )
+            .into(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BodyHandle(usize);
+
+/// A simple impulse based physics world. Add bodies, then call `step` with `ctx.delta_time()`
+/// every frame.
+pub struct World {
+    // game units per second per second, positive y is down
+    pub gravity: [f32; 2],
+    // each step is split into this many smaller ones, more is steadier but slower
+    pub substeps: u32,
+    bodies: Vec<Option<Body>>,
+    contacts: Vec<(BodyHandle, BodyHandle, Contact)>,
+    // kept between substeps so bodies that haven't moved, like the static ones, are left alone
+    hash: SpatialHash<usize>,
+}
+
+impl World {
+    pub fn new(gravity: [f32; 2]) -> Self {
+        World {
+            gravity,
+            substeps: 4,
+            bodies: Vec::new(),
+            contacts: Vec::new(),
+            hash: SpatialHash::new(1.0),
+        }
+    }
+
+    pub fn add(&mut self, body: Body) -> BodyHandle {
+        // reuse a removed body's slot if there is one
+        match self.bodies.iter().position(Option::is_none) {
+            Some(index) => {
+                self.bodies[index] = Some(body);
+                BodyHandle(index)
+            }
+            None => {
+                self.bodies.push(Some(body));
+                BodyHandle(self.bodies.len() - 1)
+            }
+        }
+    }
+
+    pub fn remove(&mut self, handle: BodyHandle) -> Option<Body> {
+        self.hash.remove(handle.0);
+        self.bodies.get_mut(handle.0).and_then(Option::take)
+    }
+
+    pub fn body(&self, handle: BodyHandle) -> Option<&Body> {
+        self.bodies.get(handle.0).and_then(Option::as_ref)
+    }
+
+    pub fn body_mut(&mut self, handle: BodyHandle) -> Option<&mut Body> {
+        self.bodies.get_mut(handle.0).and_then(Option::as_mut)
+    }
+
+    pub fn bodies(&self) -> impl Iterator<Item = (BodyHandle, &Body)> {
+        self.bodies
+            .iter()
+            .enumerate()
+            .filter_map(|(i, body)| body.as_ref().map(|body| (BodyHandle(i), body)))
+    }
+
+    // pairs of bodies touching at the end of the last step, the normal pointing from the first
+    // to the second
+    pub fn contacts(&self) -> &[(BodyHandle, BodyHandle, Contact)] {
+        &self.contacts
+    }
+
+    // `dt` is in seconds
+    pub fn step(&mut self, dt: f32) {
+        let substeps = self.substeps.max(1);
+        let h = dt / substeps as f32;
+        for substep in 0..substeps {
+            self.integrate(h);
+            self.solve(substep + 1 == substeps);
+        }
+    }
+
+    fn integrate(&mut self, h: f32) {
+        for body in self.bodies.iter_mut().flatten() {
+            if body.kind == BodyKind::Static {
+                continue;
+            }
+            if body.kind == BodyKind::Dynamic {
+                body.velocity[0] += self.gravity[0] * h;
+                body.velocity[1] += self.gravity[1] * h;
+            }
+            body.position[0] += body.velocity[0] * h;
+            body.position[1] += body.velocity[1] * h;
+            body.angle += body.angular_velocity * h;
+        }
+    }
+
+    fn solve(&mut self, record_contacts: bool) {
+        if record_contacts {
+            self.contacts.clear();
+        }
+
+        let shapes = self
+            .bodies
+            .iter()
+            .map(|body| body.as_ref().map(Body::shape))
+            .collect::<Vec<_>>();
+        let bounds = shapes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, shape)| shape.as_ref().map(|shape| (i, shape.bounds())))
+            .collect::<Vec<(usize, Aabb)>>();
+        if bounds.len() < 2 {
+            return;
+        }
+
+        // Cells about the size of a typical body. That's the median rather than the mean, so one
+        // long floor doesn't make every cell huge; the hash keeps bodies that big aside instead.
+        let mut sizes = bounds
+            .iter()
+            .map(|(_, b)| (b.max[0] - b.min[0]).max(b.max[1] - b.min[1]))
+            .collect::<Vec<_>>();
+        sizes.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let size = sizes[sizes.len() / 2];
+        // only rebuilt when the typical size has changed a lot
+        let cell_size = self.hash.cell_size();
+        if !(size > cell_size / 2.0 && size < cell_size * 2.0) {
+            self.hash = SpatialHash::new(size);
+        }
+        for &(i, aabb) in &bounds {
+            if self.hash.bounds(i) != Some(&aabb) {
+                self.hash.insert(i, aabb);
+            }
+        }
+
+        for (a, b) in self.hash.pairs() {
+            let (body_a, body_b) = match (&self.bodies[a], &self.bodies[b]) {
+                (Some(body_a), Some(body_b)) => (body_a, body_b),
+                _ => continue,
+            };
+            let inverse_mass = body_a.inverse_mass + body_b.inverse_mass;
+            if inverse_mass == 0.0 {
+                continue;
+            }
+            let contact = match (&shapes[a], &shapes[b]) {
+                (Some(shape_a), Some(shape_b)) => match shape_a.contact(shape_b) {
+                    Some(contact) => contact,
+                    None => continue,
+                },
+                _ => continue,
+            };
+            if record_contacts {
+                self.contacts.push((BodyHandle(a), BodyHandle(b), contact));
+            }
+
+            let n = contact.normal;
+            let relative = [
+                body_b.velocity[0] - body_a.velocity[0],
+                body_b.velocity[1] - body_a.velocity[1],
+            ];
+            let along_normal = relative[0] * n[0] + relative[1] * n[1];
+
+            let mut impulse = [0.0, 0.0];
+            // only push apart bodies that are moving together
+            if along_normal < 0.0 {
+                let restitution = body_a.restitution.min(body_b.restitution);
+                let j = -(1.0 + restitution) * along_normal / inverse_mass;
+                impulse = [n[0] * j, n[1] * j];
+
+                // friction along the surface, no stronger than the push apart
+                let tangent = [
+                    relative[0] - n[0] * along_normal,
+                    relative[1] - n[1] * along_normal,
+                ];
+                let tangent_length = (tangent[0] * tangent[0] + tangent[1] * tangent[1]).sqrt();
+                if tangent_length > 0.0 {
+                    let t = [tangent[0] / tangent_length, tangent[1] / tangent_length];
+                    let friction = (body_a.friction * body_b.friction).sqrt();
+                    let jt = (-(relative[0] * t[0] + relative[1] * t[1]) / inverse_mass)
+                        .max(-j * friction)
+                        .min(j * friction);
+                    impulse[0] += t[0] * jt;
+                    impulse[1] += t[1] * jt;
+                }
+            }
+
+            let push = (contact.depth - SLOP).max(0.0) / inverse_mass * CORRECTION;
+            let correction = [n[0] * push, n[1] * push];
+
+            for &(index, sign) in &[(a, -1.0), (b, 1.0)] {
+                if let Some(body) = self.bodies[index].as_mut() {
+                    let inverse_mass = body.inverse_mass;
+                    body.velocity[0] += sign * impulse[0] * inverse_mass;
+                    body.velocity[1] += sign * impulse[1] * inverse_mass;
+                    body.position[0] += sign * correction[0] * inverse_mass;
+                    body.position[1] += sign * correction[1] * inverse_mass;
+                }
+            }
+        }
+    }
+
+    // Outlines every collider with the shape functions, with a line from each circle's center
+    // showing its angle.
+    pub fn debug_draw(
+        &self,
+        ctx: &mut GraphicsContext,
+        line_width: f32,
+        paint: impl Into<Paint>,
+    ) -> PumiceResult<()> {
+        let paint = paint.into();
+        for (_, body) in self.bodies() {
+            match body.shape() {
+                Shape::Circle(circle) => {
+                    ctx.new_circle_outline(circle.center, circle.radius, line_width, paint)?;
+                    let (sin, cos) = body.angle.to_radians().sin_cos();
+                    let tip = [
+                        circle.center[0] + cos * circle.radius,
+                        circle.center[1] + sin * circle.radius,
+                    ];
+                    draw_line(ctx, circle.center, tip, line_width, paint)?;
+                }
+                Shape::OrientedRect(rect) => {
+                    ctx.new_rectangle_outline_full(
+                        rect.pos,
+                        rect.sides,
+                        line_width,
+                        paint,
+                        Some(rect.rot),
+                    )?;
+                }
+                Shape::Polygon(polygon) => {
+                    let points = &polygon.points;
+                    for (&from, &to) in points.iter().zip(points.iter().cycle().skip(1)) {
+                        draw_line(ctx, from, to, line_width, paint)?;
+                    }
+                }
+                Shape::Aabb(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+// a rectangle `width` thick from `from` to `to`, turned about `from`
+fn draw_line(
+    ctx: &mut GraphicsContext,
+    from: [f32; 2],
+    to: [f32; 2],
+    width: f32,
+    paint: Paint,
+) -> PumiceResult<()> {
+    let d = [to[0] - from[0], to[1] - from[1]];
+    ctx.new_rectangle_full(
+        [from[0], from[1] - width / 2.0],
+        [(d[0] * d[0] + d[1] * d[1]).sqrt(), width],
+        paint,
+        Some(Rotation {
+            degrees: d[1].atan2(d[0]).to_degrees(),
+            point: from,
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    // a 4 wide floor whose top is at y = 1, and a ball of radius 0.1 above it
+    fn drop_ball(restitution: f32) -> (World, BodyHandle, BodyHandle) {
+        let mut world = World::new([0.0, 10.0]);
+        let floor = world
+            .add(Body::fixed([0.0, 1.1], Collider::Rectangle([4.0, 0.2])).restitution(restitution));
+        let ball = world.add(
+            Body::dynamic([0.0, 0.0], Collider::Circle(0.1))
+                .restitution(restitution)
+                .friction(0.0),
+        );
+        (world, floor, ball)
+    }
+
+    #[test]
+    fn comes_to_rest_on_a_floor() {
+        let (mut world, floor, ball) = drop_ball(0.5);
+        for _ in 0..300 {
+            world.step(DT);
+        }
+        let body = world.body(ball).unwrap();
+        assert!(body.velocity[1].abs() < 0.05, "{:?}", body.velocity);
+        assert!((body.position[1] - 0.9).abs() < 0.01, "{:?}", body.position);
+        assert_eq!(world.body(floor).unwrap().position, [0.0, 1.1]);
+        assert_eq!(world.contacts().len(), 1);
+    }
+
+    // the highest the ball gets after first reaching the floor
+    fn bounce_height(restitution: f32) -> f32 {
+        let (mut world, _, ball) = drop_ball(restitution);
+        let mut touched = false;
+        let mut highest = f32::INFINITY;
+        for _ in 0..120 {
+            world.step(DT);
+            let y = world.body(ball).unwrap().position[1];
+            touched |= y > 0.85;
+            if touched {
+                highest = highest.min(y);
+            }
+        }
+        assert!(touched);
+        highest
+    }
+
+    #[test]
+    fn restitution() {
+        // dropped from 0.8 above where it rests
+        assert!(bounce_height(0.0) > 0.85);
+        assert!(bounce_height(1.0) < 0.1);
+    }
+
+    #[test]
+    fn contacts_dont_move_static_or_kinematic_bodies() {
+        let mut world = World::new([0.0, 10.0]);
+        let wall = world.add(Body::fixed([0.0, 1.0], Collider::Rectangle([2.0, 0.2])));
+        let mut platform = Body::kinematic([3.0, 1.0], Collider::Rectangle([2.0, 0.2]));
+        platform.velocity = [-0.5, 0.0];
+        let platform = world.add(platform);
+        for i in 0..10 {
+            let x = i as f32 * 0.5;
+            world.add(Body::dynamic([x, 0.5], Collider::Circle(0.1)).mass(100.0));
+        }
+        // and one sitting between the two from the start
+        world.add(Body::dynamic([1.5, 1.0], Collider::Rectangle([0.4, 0.4])));
+        // a kinematic body overlapping a static one is left alone too
+        world.add(Body::kinematic([0.0, 1.05], Collider::Circle(0.2)));
+
+        for _ in 0..120 {
+            world.step(DT);
+        }
+        assert!(!world.contacts().is_empty());
+        assert_eq!(world.body(wall).unwrap().position, [0.0, 1.0]);
+        assert_eq!(world.body(wall).unwrap().velocity, [0.0, 0.0]);
+        let platform = world.body(platform).unwrap();
+        assert_eq!(platform.velocity, [-0.5, 0.0]);
+        assert!(
+            (platform.position[0] - 2.0).abs() < 1e-3,
+            "{:?}",
+            platform.position
+        );
+        assert_eq!(platform.position[1], 1.0);
+    }
+
+    #[test]
+    fn one_big_body_doesnt_make_the_cells_big() {
+        let mut world = World::new([0.0, 10.0]);
+        world.add(Body::fixed(
+            [0.0, 1.0],
+            Collider::Rectangle([10_000.0, 0.2]),
+        ));
+        for i in 0..100 {
+            world.add(Body::dynamic(
+                [i as f32 * 0.3 - 15.0, 0.5],
+                Collider::Circle(0.1),
+            ));
+        }
+        for _ in 0..60 {
+            world.step(DT);
+        }
+        assert!(world.hash.cell_size() < 1.0);
+        // every ball has landed on the floor
+        assert_eq!(world.contacts().len(), 100);
+    }
+
+    #[test]
+    fn removed_bodies_stop_colliding() {
+        let (mut world, floor, ball) = drop_ball(0.0);
+        for _ in 0..60 {
+            world.step(DT);
+        }
+        world.remove(floor);
+        world.step(DT);
+        assert!(world.contacts().is_empty());
+        assert!(world.body(ball).unwrap().velocity[1] > 0.0);
+    }
+}
@@ -0,0 +1,667 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+use vulkano::buffer::{BufferSlice, BufferUsage, CpuAccessibleBuffer, CpuBufferPool};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::descriptor::PipelineLayoutAbstract;
+use vulkano::device::{Device, Queue};
+use vulkano::format::ClearValue;
+use vulkano::framebuffer::{Framebuffer, FramebufferAbstract, RenderPassAbstract, Subpass};
+use vulkano::image::swapchain::SwapchainImage;
+use vulkano::image::AttachmentImage;
+use vulkano::pipeline::vertex::{OneVertexOneInstanceDefinition, SingleBufferDefinition};
+use vulkano::pipeline::viewport::Viewport;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::swapchain::{
+    AcquireError, PresentMode, SupportedPresentModes, Surface, SurfaceTransform, Swapchain,
+    SwapchainAcquireFuture, SwapchainCreationError,
+};
+use vulkano::sync::{FlushError, GpuFuture};
+
+use lyon::tessellation::VertexBuffers;
+
+use winit::Window;
+
+use crate::error::{PumiceError, PumiceResult};
+use crate::instancing::{InstanceBatch, MeshInstance, MeshVertex};
+use crate::{fs, ivs, vs, Vertex};
+
+type Pipeline = GraphicsPipeline<
+    SingleBufferDefinition<Vertex>,
+    Box<dyn PipelineLayoutAbstract + Send + Sync>,
+    Arc<dyn RenderPassAbstract + Send + Sync>,
+>;
+
+type InstancedPipeline = GraphicsPipeline<
+    OneVertexOneInstanceDefinition<MeshVertex, MeshInstance>,
+    Box<dyn PipelineLayoutAbstract + Send + Sync>,
+    Arc<dyn RenderPassAbstract + Send + Sync>,
+>;
+
+// the present mode is the one actually picked, see choose_present_mode
+type SwapchainParts = (
+    Arc<Swapchain<Window>>,
+    Vec<Arc<SwapchainImage<Window>>>,
+    PresentMode,
+);
+
+// shader modules belong to a device, so they're reloaded along with it
+pub(crate) struct Shaders {
+    vertex: vs::Shader,
+    instanced_vertex: ivs::Shader,
+    fragment: fs::Shader,
+}
+
+impl Shaders {
+    pub(crate) fn load(device: Arc<Device>) -> PumiceResult<Self> {
+        Ok(Shaders {
+            vertex: vs::Shader::load(device.clone())?,
+            instanced_vertex: ivs::Shader::load(device.clone())?,
+            fragment: fs::Shader::load(device)?,
+        })
+    }
+}
+
+// an acquired swapchain image waiting to be drawn to
+pub(crate) struct Frame {
+    image_num: usize,
+    acquire_future: SwapchainAcquireFuture<Window>,
+}
+
+// Everything `run` needs to draw that depends on the device or the surface. It's all rebuilt
+// from scratch if either of those is lost.
+pub(crate) struct Renderer {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    surface: Arc<Surface<Window>>,
+    swapchain: Arc<Swapchain<Window>>,
+    present_mode: PresentMode,
+    samples: u32,
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
+    pipeline: Arc<Pipeline>,
+    instanced_pipeline: Arc<InstancedPipeline>,
+    uniform_buffer: CpuBufferPool<vs::ty::Data>,
+    dynamic_state: DynamicState,
+    previous_frame_end: Option<Box<dyn GpuFuture>>,
+    pub(crate) recreate_swapchain: bool,
+}
+
+impl Renderer {
+    pub(crate) fn new(
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        surface: Arc<Surface<Window>>,
+        shaders: &Shaders,
+        present_mode: PresentMode,
+        samples: u32,
+    ) -> PumiceResult<Self> {
+        let dimensions = surface
+            .capabilities(device.physical_device())?
+            .current_extent
+            .unwrap_or([1280, 1024]);
+
+        let (swapchain, images, present_mode) = create_swapchain(
+            device.clone(),
+            surface.clone(),
+            &queue,
+            dimensions,
+            present_mode,
+            None,
+        )?;
+
+        let render_pass = if samples > 1 {
+            Arc::new(
+                vulkano::single_pass_renderpass!(device.clone(),
+                attachments: {
+                    multisampled: {
+                        load: Clear,
+                        store: DontCare,
+                        format: swapchain.format(),
+                        samples: samples,
+                    },
+                    color: {
+                        load: DontCare,
+                        store: Store,
+                        format: swapchain.format(),
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [multisampled],
+                    depth_stencil: {},
+                    resolve: [color],
+                }
+                )
+                .map_err(PumiceError::pipeline)?,
+            ) as Arc<dyn RenderPassAbstract + Send + Sync>
+        } else {
+            Arc::new(
+                vulkano::single_pass_renderpass!(device.clone(),
+                attachments: {
+                    color: {
+                        load: Clear,
+                        store: Store,
+                        format: swapchain.format(),
+                        samples: 1,
+                    }
+                },
+                pass: {
+                    color: [color],
+                    depth_stencil: {}
+                }
+                )
+                .map_err(PumiceError::pipeline)?,
+            ) as Arc<dyn RenderPassAbstract + Send + Sync>
+        };
+
+        let mut dynamic_state = DynamicState::none();
+        let framebuffers = window_size_dependent_setup(
+            device.clone(),
+            &images,
+            render_pass.clone(),
+            samples,
+            &mut dynamic_state,
+        )?;
+
+        let pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input_single_buffer::<Vertex>()
+                .vertex_shader(shaders.vertex.main_entry_point(), ())
+                .viewports_dynamic_scissors_irrelevant(1)
+                .fragment_shader(shaders.fragment.main_entry_point(), ())
+                .blend_alpha_blending()
+                // the render pass is built above with a single subpass
+                .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+                .build(device.clone())
+                .map_err(PumiceError::pipeline)?,
+        );
+
+        let instanced_pipeline = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input(OneVertexOneInstanceDefinition::<MeshVertex, MeshInstance>::new())
+                .vertex_shader(shaders.instanced_vertex.main_entry_point(), ())
+                .viewports_dynamic_scissors_irrelevant(1)
+                .fragment_shader(shaders.fragment.main_entry_point(), ())
+                .blend_alpha_blending()
+                .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+                .build(device.clone())
+                .map_err(PumiceError::pipeline)?,
+        );
+
+        let uniform_buffer = CpuBufferPool::<vs::ty::Data>::new(device.clone(), BufferUsage::all());
+        let previous_frame_end = Some(Box::new(vulkano::sync::now(device.clone())) as Box<_>);
+
+        Ok(Renderer {
+            device,
+            queue,
+            surface,
+            swapchain,
+            present_mode,
+            samples,
+            render_pass,
+            framebuffers,
+            pipeline,
+            instanced_pipeline,
+            uniform_buffer,
+            dynamic_state,
+            previous_frame_end,
+            recreate_swapchain: false,
+        })
+    }
+
+    // Dropping the last frame's future waits on its fence, which panics once the device is lost,
+    // so it's leaked instead
+    pub(crate) fn forget_in_flight_frame(&mut self) {
+        if let Some(previous_frame_end) = self.previous_frame_end.take() {
+            std::mem::forget(previous_frame_end);
+        }
+    }
+
+    pub(crate) fn present_mode(&self) -> PresentMode {
+        self.present_mode
+    }
+
+    pub(crate) fn set_present_mode(&mut self, present_mode: PresentMode) -> PumiceResult<()> {
+        let (swapchain, images, present_mode) = create_swapchain(
+            self.device.clone(),
+            self.surface.clone(),
+            &self.queue,
+            self.swapchain.dimensions(),
+            present_mode,
+            Some(&self.swapchain),
+        )?;
+
+        self.swapchain = swapchain;
+        self.present_mode = present_mode;
+        self.framebuffers = window_size_dependent_setup(
+            self.device.clone(),
+            &images,
+            self.render_pass.clone(),
+            self.samples,
+            &mut self.dynamic_state,
+        )?;
+        Ok(())
+    }
+
+    // Recreates the swapchain if the window changed size. Returns false if there's nothing to
+    // draw to this frame, e.g. while the window is minimized.
+    fn resize(&mut self) -> PumiceResult<bool> {
+        if !self.recreate_swapchain {
+            return Ok(true);
+        }
+
+        let (window_size, hidpi_factor) = {
+            let window = self.surface.window();
+            (window.get_inner_size(), window.get_hidpi_factor())
+        };
+
+        let dimensions = match window_size {
+            Some(dimensions) => {
+                let dimensions: (u32, u32) = dimensions.to_physical(hidpi_factor).into();
+                [dimensions.0, dimensions.1]
+            }
+            None => return Ok(false),
+        };
+
+        let (swapchain, images) = match self.swapchain.recreate_with_dimension(dimensions) {
+            Ok(r) => r,
+            Err(SwapchainCreationError::UnsupportedDimensions) => return Ok(false),
+            Err(err) => return Err(err.into()),
+        };
+
+        self.swapchain = swapchain;
+        self.framebuffers = window_size_dependent_setup(
+            self.device.clone(),
+            &images,
+            self.render_pass.clone(),
+            self.samples,
+            &mut self.dynamic_state,
+        )?;
+
+        self.recreate_swapchain = false;
+        Ok(true)
+    }
+
+    pub(crate) fn acquire(&mut self) -> PumiceResult<Option<Frame>> {
+        if let Some(previous_frame_end) = self.previous_frame_end.as_mut() {
+            previous_frame_end.cleanup_finished();
+        }
+
+        if !self.resize()? {
+            return Ok(None);
+        }
+
+        match vulkano::swapchain::acquire_next_image(self.swapchain.clone(), None) {
+            Ok((image_num, acquire_future)) => Ok(Some(Frame {
+                image_num,
+                acquire_future,
+            })),
+            Err(AcquireError::OutOfDate) => {
+                self.recreate_swapchain = true;
+                Ok(None)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    // Regular geometry first, then each instance batch on top. Returns the number of draw calls
+    // made.
+    pub(crate) fn draw(
+        &mut self,
+        frame: Frame,
+        geometry: &VertexBuffers<Vertex, u16>,
+        instance_batches: &[InstanceBatch],
+        scale: [f32; 2],
+        clear_color: [f32; 4],
+    ) -> PumiceResult<u32> {
+        let previous_frame_end = self
+            .previous_frame_end
+            .take()
+            .unwrap_or_else(|| Box::new(vulkano::sync::now(self.device.clone())));
+        self.previous_frame_end = Some(Box::new(vulkano::sync::now(self.device.clone())));
+
+        let uniform_buffer_subbuffer = self.uniform_buffer.next(vs::ty::Data { scale })?;
+
+        let set = Arc::new(
+            PersistentDescriptorSet::start(self.pipeline.clone(), 0)
+                .add_buffer(uniform_buffer_subbuffer.clone())
+                .map_err(PumiceError::render)?
+                .build()
+                .map_err(PumiceError::render)?,
+        );
+        let instanced_set = Arc::new(
+            PersistentDescriptorSet::start(self.instanced_pipeline.clone(), 0)
+                .add_buffer(uniform_buffer_subbuffer)
+                .map_err(PumiceError::render)?
+                .build()
+                .map_err(PumiceError::render)?,
+        );
+
+        let clear_values = if self.samples > 1 {
+            vec![clear_color.into(), ClearValue::None]
+        } else {
+            vec![clear_color.into()]
+        };
+
+        let mut draw_calls = 0;
+        let mut builder = AutoCommandBufferBuilder::primary_one_time_submit(
+            self.device.clone(),
+            self.queue.family(),
+        )?
+        .begin_render_pass(
+            self.framebuffers[frame.image_num].clone(),
+            false,
+            clear_values,
+        )
+        .map_err(PumiceError::render)?;
+
+        // uploaded once and drawn a slice at a time between the instance batches. Empty buffers
+        // can't be created, and there'd be nothing to draw anyway.
+        let geometry_buffers = if geometry.indices.is_empty() {
+            None
+        } else {
+            let vertex_buffer = CpuAccessibleBuffer::from_iter(
+                self.device.clone(),
+                BufferUsage::all(),
+                geometry.vertices.iter().cloned(),
+            )?;
+            let index_buffer = CpuAccessibleBuffer::from_iter(
+                self.device.clone(),
+                BufferUsage::all(),
+                geometry.indices.iter().cloned(),
+            )?;
+            Some((vertex_buffer, index_buffer))
+        };
+
+        let batch_starts = instance_batches.iter().map(|batch| batch.first_index);
+        for step in draw_steps(geometry.indices.len(), batch_starts) {
+            match step {
+                DrawStep::Geometry(range) => {
+                    let (vertex_buffer, index_buffer) = geometry_buffers.as_ref().unwrap();
+                    let indices = BufferSlice::from_typed_buffer_access(index_buffer.clone())
+                        .slice(range)
+                        .unwrap();
+                    builder = builder
+                        .draw_indexed(
+                            self.pipeline.clone(),
+                            &self.dynamic_state,
+                            vertex_buffer.clone(),
+                            indices,
+                            set.clone(),
+                            (),
+                        )
+                        .map_err(PumiceError::render)?;
+                }
+                DrawStep::Instanced(i) => {
+                    let batch = &instance_batches[i];
+                    let vertex_buffer = CpuAccessibleBuffer::from_iter(
+                        self.device.clone(),
+                        BufferUsage::all(),
+                        batch.vertices.iter().cloned(),
+                    )?;
+                    let instance_buffer = CpuAccessibleBuffer::from_iter(
+                        self.device.clone(),
+                        BufferUsage::all(),
+                        batch.instances.iter().cloned(),
+                    )?;
+                    let index_buffer = CpuAccessibleBuffer::from_iter(
+                        self.device.clone(),
+                        BufferUsage::all(),
+                        batch.indices.iter().cloned(),
+                    )?;
+
+                    builder = builder
+                        .draw_indexed(
+                            self.instanced_pipeline.clone(),
+                            &self.dynamic_state,
+                            (vertex_buffer, instance_buffer),
+                            index_buffer,
+                            instanced_set.clone(),
+                            (),
+                        )
+                        .map_err(PumiceError::render)?;
+                }
+            }
+            draw_calls += 1;
+        }
+
+        let command_buffer = builder
+            .end_render_pass()
+            .map_err(PumiceError::render)?
+            .build()
+            .map_err(PumiceError::render)?;
+
+        let future = previous_frame_end
+            .join(frame.acquire_future)
+            .then_execute(self.queue.clone(), command_buffer)
+            .map_err(PumiceError::render)?
+            .then_swapchain_present(self.queue.clone(), self.swapchain.clone(), frame.image_num)
+            .then_signal_fence_and_flush();
+
+        match future {
+            Ok(future) => {
+                self.previous_frame_end = Some(Box::new(future) as Box<_>);
+            }
+            Err(FlushError::OutOfDate) => {
+                self.recreate_swapchain = true;
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        Ok(draw_calls)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum DrawStep {
+    // a range of the regular geometry's indices
+    Geometry(Range<usize>),
+    // an index into the instance batches
+    Instanced(usize),
+}
+
+// Splits the regular geometry up around the instance batches, so everything is drawn in the order
+// it was added. `batch_starts` is how many geometry indices there were when each batch was added.
+fn draw_steps(index_count: usize, batch_starts: impl IntoIterator<Item = usize>) -> Vec<DrawStep> {
+    let mut steps = Vec::new();
+    let mut drawn = 0;
+    for (i, start) in batch_starts.into_iter().enumerate() {
+        if start > drawn {
+            steps.push(DrawStep::Geometry(drawn..start));
+            drawn = start;
+        }
+        steps.push(DrawStep::Instanced(i));
+    }
+    if index_count > drawn {
+        steps.push(DrawStep::Geometry(drawn..index_count));
+    }
+    steps
+}
+
+// Fifo is the only mode every surface has to support, so everything falls back to it. Mailbox and
+// Immediate both avoid waiting on vsync so they're tried in place of each other first.
+fn choose_present_mode(requested: PresentMode, supported: SupportedPresentModes) -> PresentMode {
+    let fallbacks = match requested {
+        PresentMode::Mailbox => vec![PresentMode::Mailbox, PresentMode::Immediate],
+        PresentMode::Immediate => vec![PresentMode::Immediate, PresentMode::Mailbox],
+        PresentMode::Relaxed => vec![PresentMode::Relaxed],
+        PresentMode::Fifo => vec![],
+    };
+
+    fallbacks
+        .into_iter()
+        .find(|&mode| supported.supports(mode))
+        .unwrap_or(PresentMode::Fifo)
+}
+
+fn create_swapchain(
+    device: Arc<Device>,
+    surface: Arc<Surface<Window>>,
+    queue: &Arc<Queue>,
+    dimensions: [u32; 2],
+    present_mode: PresentMode,
+    old_swapchain: Option<&Arc<Swapchain<Window>>>,
+) -> PumiceResult<SwapchainParts> {
+    let caps = surface.capabilities(device.physical_device())?;
+    let alpha = caps
+        .supported_composite_alpha
+        .iter()
+        .next()
+        .ok_or(SwapchainCreationError::UnsupportedCompositeAlpha)?;
+    let format = caps
+        .supported_formats
+        .first()
+        .ok_or(SwapchainCreationError::UnsupportedFormat)?
+        .0;
+    let present_mode = choose_present_mode(present_mode, caps.present_modes);
+
+    let (swapchain, images) = Swapchain::new(
+        device,
+        surface,
+        caps.min_image_count,
+        format,
+        dimensions,
+        1,
+        caps.supported_usage_flags,
+        queue,
+        SurfaceTransform::Identity,
+        alpha,
+        present_mode,
+        true,
+        old_swapchain,
+    )?;
+
+    Ok((swapchain, images, present_mode))
+}
+
+fn window_size_dependent_setup(
+    device: Arc<Device>,
+    images: &[Arc<SwapchainImage<Window>>],
+    render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    samples: u32,
+    dynamic_state: &mut DynamicState,
+) -> PumiceResult<Vec<Arc<dyn FramebufferAbstract + Send + Sync>>> {
+    let dimensions = images[0].dimensions();
+
+    let viewport = Viewport {
+        origin: [0.0, 0.0],
+        dimensions: [dimensions[0] as f32, dimensions[1] as f32],
+        depth_range: 0.0..1.0,
+    };
+    dynamic_state.viewports = Some(vec![viewport]);
+
+    // drawn into at full sample count, then resolved into the swapchain image
+    let multisampled = if samples > 1 {
+        let image = AttachmentImage::transient_multisampled(
+            device,
+            dimensions,
+            samples,
+            images[0].swapchain().format(),
+        )
+        .map_err(PumiceError::render)?;
+        Some(image)
+    } else {
+        None
+    };
+
+    let mut framebuffers = Vec::with_capacity(images.len());
+    for image in images {
+        let framebuffer = match &multisampled {
+            Some(multisampled) => Arc::new(
+                Framebuffer::start(render_pass.clone())
+                    .add(multisampled.clone())
+                    .map_err(PumiceError::render)?
+                    .add(image.clone())
+                    .map_err(PumiceError::render)?
+                    .build()
+                    .map_err(PumiceError::render)?,
+            ) as Arc<dyn FramebufferAbstract + Send + Sync>,
+            None => Arc::new(
+                Framebuffer::start(render_pass.clone())
+                    .add(image.clone())
+                    .map_err(PumiceError::render)?
+                    .build()
+                    .map_err(PumiceError::render)?,
+            ) as Arc<dyn FramebufferAbstract + Send + Sync>,
+        };
+        framebuffers.push(framebuffer);
+    }
+
+    Ok(framebuffers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batches_are_drawn_between_geometry() {
+        use DrawStep::*;
+
+        assert_eq!(draw_steps(0, []), vec![]);
+        assert_eq!(draw_steps(6, []), vec![Geometry(0..6)]);
+        assert_eq!(
+            draw_steps(12, [6]),
+            vec![Geometry(0..6), Instanced(0), Geometry(6..12)]
+        );
+        // batches before any geometry, two in a row and one at the end
+        assert_eq!(
+            draw_steps(9, [0, 3, 3, 9]),
+            vec![
+                Instanced(0),
+                Geometry(0..3),
+                Instanced(1),
+                Instanced(2),
+                Geometry(3..9),
+                Instanced(3),
+            ]
+        );
+        assert_eq!(draw_steps(0, [0]), vec![Instanced(0)]);
+    }
+
+    #[test]
+    fn present_modes_fall_back_to_fifo() {
+        let mut supported = SupportedPresentModes::none();
+        supported.fifo = true;
+        for &mode in &[
+            PresentMode::Mailbox,
+            PresentMode::Immediate,
+            PresentMode::Relaxed,
+            PresentMode::Fifo,
+        ] {
+            assert_eq!(choose_present_mode(mode, supported), PresentMode::Fifo);
+        }
+    }
+
+    #[test]
+    fn present_modes_try_similar_modes_first() {
+        let mut supported = SupportedPresentModes::none();
+        supported.fifo = true;
+        supported.immediate = true;
+        assert_eq!(
+            choose_present_mode(PresentMode::Mailbox, supported),
+            PresentMode::Immediate
+        );
+        assert_eq!(
+            choose_present_mode(PresentMode::Immediate, supported),
+            PresentMode::Immediate
+        );
+
+        supported.mailbox = true;
+        supported.relaxed = true;
+        assert_eq!(
+            choose_present_mode(PresentMode::Mailbox, supported),
+            PresentMode::Mailbox
+        );
+        assert_eq!(
+            choose_present_mode(PresentMode::Relaxed, supported),
+            PresentMode::Relaxed
+        );
+        assert_eq!(
+            choose_present_mode(PresentMode::Fifo, supported),
+            PresentMode::Fifo
+        );
+    }
+}
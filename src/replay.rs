@@ -0,0 +1,526 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use winit::dpi::{LogicalPosition, LogicalSize};
+use winit::{
+    DeviceEvent, DeviceId, ElementState, KeyboardInput, ModifiersState, MouseButton,
+    MouseScrollDelta, Touch, TouchPhase, WindowEvent, WindowId,
+};
+
+use crate::error::{PumiceError, PumiceResult};
+use crate::event::Event;
+use crate::gamepad::GamepadEvent;
+
+// winit's events hold opaque window and device ids, so they're recorded without them and replayed
+// with dummy ids. Everything else is kept as is.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+enum RecordedEvent {
+    Window(RecordedWindowEvent),
+    Device(RecordedDeviceEvent),
+    Awakened,
+    Suspended(bool),
+    Gamepad(GamepadEvent),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+enum RecordedWindowEvent {
+    Resized(LogicalSize),
+    Moved(LogicalPosition),
+    CloseRequested,
+    Destroyed,
+    DroppedFile(PathBuf),
+    HoveredFile(PathBuf),
+    HoveredFileCancelled,
+    ReceivedCharacter(char),
+    Focused(bool),
+    KeyboardInput(KeyboardInput),
+    CursorMoved {
+        position: LogicalPosition,
+        modifiers: ModifiersState,
+    },
+    CursorEntered,
+    CursorLeft,
+    MouseWheel {
+        delta: MouseScrollDelta,
+        phase: TouchPhase,
+        modifiers: ModifiersState,
+    },
+    MouseInput {
+        state: ElementState,
+        button: MouseButton,
+        modifiers: ModifiersState,
+    },
+    TouchpadPressure {
+        pressure: f32,
+        stage: i64,
+    },
+    AxisMotion {
+        axis: u32,
+        value: f64,
+    },
+    Refresh,
+    Touch {
+        phase: TouchPhase,
+        location: LogicalPosition,
+        id: u64,
+    },
+    HiDpiFactorChanged(f64),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+enum RecordedDeviceEvent {
+    Added,
+    Removed,
+    MouseMotion { delta: (f64, f64) },
+    MouseWheel { delta: MouseScrollDelta },
+    Motion { axis: u32, value: f64 },
+    Button { button: u32, state: ElementState },
+    Key(KeyboardInput),
+    Text { codepoint: char },
+}
+
+// Each line holds either an event or the frame's delta time. Recordings from before delta times
+// were kept only have events, and replay with the live frame times.
+#[derive(Serialize, Deserialize)]
+struct RecordedLine {
+    frame: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    event: Option<RecordedEvent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    delta_time: Option<f32>,
+}
+
+impl From<&Event> for RecordedEvent {
+    fn from(event: &Event) -> Self {
+        match event {
+            Event::Winit(winit::Event::WindowEvent { event, .. }) => {
+                RecordedEvent::Window(RecordedWindowEvent::from(event))
+            }
+            Event::Winit(winit::Event::DeviceEvent { event, .. }) => {
+                RecordedEvent::Device(RecordedDeviceEvent::from(event))
+            }
+            Event::Winit(winit::Event::Awakened) => RecordedEvent::Awakened,
+            Event::Winit(winit::Event::Suspended(suspended)) => {
+                RecordedEvent::Suspended(*suspended)
+            }
+            Event::Gamepad(event) => RecordedEvent::Gamepad(*event),
+        }
+    }
+}
+
+impl From<&WindowEvent> for RecordedWindowEvent {
+    fn from(event: &WindowEvent) -> Self {
+        match event.clone() {
+            WindowEvent::Resized(size) => RecordedWindowEvent::Resized(size),
+            WindowEvent::Moved(pos) => RecordedWindowEvent::Moved(pos),
+            WindowEvent::CloseRequested => RecordedWindowEvent::CloseRequested,
+            WindowEvent::Destroyed => RecordedWindowEvent::Destroyed,
+            WindowEvent::DroppedFile(path) => RecordedWindowEvent::DroppedFile(path),
+            WindowEvent::HoveredFile(path) => RecordedWindowEvent::HoveredFile(path),
+            WindowEvent::HoveredFileCancelled => RecordedWindowEvent::HoveredFileCancelled,
+            WindowEvent::ReceivedCharacter(c) => RecordedWindowEvent::ReceivedCharacter(c),
+            WindowEvent::Focused(focused) => RecordedWindowEvent::Focused(focused),
+            WindowEvent::KeyboardInput { input, .. } => RecordedWindowEvent::KeyboardInput(input),
+            WindowEvent::CursorMoved {
+                position,
+                modifiers,
+                ..
+            } => RecordedWindowEvent::CursorMoved {
+                position,
+                modifiers,
+            },
+            WindowEvent::CursorEntered { .. } => RecordedWindowEvent::CursorEntered,
+            WindowEvent::CursorLeft { .. } => RecordedWindowEvent::CursorLeft,
+            WindowEvent::MouseWheel {
+                delta,
+                phase,
+                modifiers,
+                ..
+            } => RecordedWindowEvent::MouseWheel {
+                delta,
+                phase,
+                modifiers,
+            },
+            WindowEvent::MouseInput {
+                state,
+                button,
+                modifiers,
+                ..
+            } => RecordedWindowEvent::MouseInput {
+                state,
+                button,
+                modifiers,
+            },
+            WindowEvent::TouchpadPressure {
+                pressure, stage, ..
+            } => RecordedWindowEvent::TouchpadPressure { pressure, stage },
+            WindowEvent::AxisMotion { axis, value, .. } => {
+                RecordedWindowEvent::AxisMotion { axis, value }
+            }
+            WindowEvent::Refresh => RecordedWindowEvent::Refresh,
+            WindowEvent::Touch(touch) => RecordedWindowEvent::Touch {
+                phase: touch.phase,
+                location: touch.location,
+                id: touch.id,
+            },
+            WindowEvent::HiDpiFactorChanged(factor) => {
+                RecordedWindowEvent::HiDpiFactorChanged(factor)
+            }
+        }
+    }
+}
+
+impl From<&DeviceEvent> for RecordedDeviceEvent {
+    fn from(event: &DeviceEvent) -> Self {
+        match event.clone() {
+            DeviceEvent::Added => RecordedDeviceEvent::Added,
+            DeviceEvent::Removed => RecordedDeviceEvent::Removed,
+            DeviceEvent::MouseMotion { delta } => RecordedDeviceEvent::MouseMotion { delta },
+            DeviceEvent::MouseWheel { delta } => RecordedDeviceEvent::MouseWheel { delta },
+            DeviceEvent::Motion { axis, value } => RecordedDeviceEvent::Motion { axis, value },
+            DeviceEvent::Button { button, state } => RecordedDeviceEvent::Button { button, state },
+            DeviceEvent::Key(input) => RecordedDeviceEvent::Key(input),
+            DeviceEvent::Text { codepoint } => RecordedDeviceEvent::Text { codepoint },
+        }
+    }
+}
+
+impl RecordedEvent {
+    fn into_event(self) -> Event {
+        // the ids are only ever compared against each other, and a replayed game only
+        // ever sees the dummy ones
+        let window_id = unsafe { WindowId::dummy() };
+        let device_id = unsafe { DeviceId::dummy() };
+
+        match self {
+            RecordedEvent::Window(event) => Event::Winit(winit::Event::WindowEvent {
+                window_id,
+                event: event.into_window_event(device_id),
+            }),
+            RecordedEvent::Device(event) => Event::Winit(winit::Event::DeviceEvent {
+                device_id,
+                event: event.into_device_event(),
+            }),
+            RecordedEvent::Awakened => Event::Winit(winit::Event::Awakened),
+            RecordedEvent::Suspended(suspended) => Event::Winit(winit::Event::Suspended(suspended)),
+            RecordedEvent::Gamepad(event) => Event::Gamepad(event),
+        }
+    }
+}
+
+impl RecordedWindowEvent {
+    fn into_window_event(self, device_id: DeviceId) -> WindowEvent {
+        match self {
+            RecordedWindowEvent::Resized(size) => WindowEvent::Resized(size),
+            RecordedWindowEvent::Moved(pos) => WindowEvent::Moved(pos),
+            RecordedWindowEvent::CloseRequested => WindowEvent::CloseRequested,
+            RecordedWindowEvent::Destroyed => WindowEvent::Destroyed,
+            RecordedWindowEvent::DroppedFile(path) => WindowEvent::DroppedFile(path),
+            RecordedWindowEvent::HoveredFile(path) => WindowEvent::HoveredFile(path),
+            RecordedWindowEvent::HoveredFileCancelled => WindowEvent::HoveredFileCancelled,
+            RecordedWindowEvent::ReceivedCharacter(c) => WindowEvent::ReceivedCharacter(c),
+            RecordedWindowEvent::Focused(focused) => WindowEvent::Focused(focused),
+            RecordedWindowEvent::KeyboardInput(input) => {
+                WindowEvent::KeyboardInput { device_id, input }
+            }
+            RecordedWindowEvent::CursorMoved {
+                position,
+                modifiers,
+            } => WindowEvent::CursorMoved {
+                device_id,
+                position,
+                modifiers,
+            },
+            RecordedWindowEvent::CursorEntered => WindowEvent::CursorEntered { device_id },
+            RecordedWindowEvent::CursorLeft => WindowEvent::CursorLeft { device_id },
+            RecordedWindowEvent::MouseWheel {
+                delta,
+                phase,
+                modifiers,
+            } => WindowEvent::MouseWheel {
+                device_id,
+                delta,
+                phase,
+                modifiers,
+            },
+            RecordedWindowEvent::MouseInput {
+                state,
+                button,
+                modifiers,
+            } => WindowEvent::MouseInput {
+                device_id,
+                state,
+                button,
+                modifiers,
+            },
+            RecordedWindowEvent::TouchpadPressure { pressure, stage } => {
+                WindowEvent::TouchpadPressure {
+                    device_id,
+                    pressure,
+                    stage,
+                }
+            }
+            RecordedWindowEvent::AxisMotion { axis, value } => WindowEvent::AxisMotion {
+                device_id,
+                axis,
+                value,
+            },
+            RecordedWindowEvent::Refresh => WindowEvent::Refresh,
+            RecordedWindowEvent::Touch {
+                phase,
+                location,
+                id,
+            } => WindowEvent::Touch(Touch {
+                device_id,
+                phase,
+                location,
+                id,
+            }),
+            RecordedWindowEvent::HiDpiFactorChanged(factor) => {
+                WindowEvent::HiDpiFactorChanged(factor)
+            }
+        }
+    }
+}
+
+impl RecordedDeviceEvent {
+    fn into_device_event(self) -> DeviceEvent {
+        match self {
+            RecordedDeviceEvent::Added => DeviceEvent::Added,
+            RecordedDeviceEvent::Removed => DeviceEvent::Removed,
+            RecordedDeviceEvent::MouseMotion { delta } => DeviceEvent::MouseMotion { delta },
+            RecordedDeviceEvent::MouseWheel { delta } => DeviceEvent::MouseWheel { delta },
+            RecordedDeviceEvent::Motion { axis, value } => DeviceEvent::Motion { axis, value },
+            RecordedDeviceEvent::Button { button, state } => DeviceEvent::Button { button, state },
+            RecordedDeviceEvent::Key(input) => DeviceEvent::Key(input),
+            RecordedDeviceEvent::Text { codepoint } => DeviceEvent::Text { codepoint },
+        }
+    }
+}
+
+/// Writes every event passed to `handle_event` to a file, one JSON object per line,
+/// tagged with the frame it was handled on, along with each frame's `delta_time`.
+pub struct Recorder {
+    writer: BufWriter<File>,
+}
+
+impl Recorder {
+    pub fn create(path: impl AsRef<Path>) -> PumiceResult<Self> {
+        match File::create(path) {
+            Ok(file) => Ok(Recorder {
+                writer: BufWriter::new(file),
+            }),
+            Err(e) => Err(PumiceError::ReplayError(format!("{}", e))),
+        }
+    }
+
+    pub fn record(&mut self, frame: u64, event: &Event) -> PumiceResult<()> {
+        self.write(RecordedLine {
+            frame,
+            event: Some(RecordedEvent::from(event)),
+            delta_time: None,
+        })
+    }
+
+    pub fn record_delta_time(&mut self, frame: u64, delta_time: f32) -> PumiceResult<()> {
+        self.write(RecordedLine {
+            frame,
+            event: None,
+            delta_time: Some(delta_time),
+        })
+    }
+
+    fn write(&mut self, line: RecordedLine) -> PumiceResult<()> {
+        serde_json::to_writer(&mut self.writer, &line)
+            .map_err(|e| PumiceError::ReplayError(format!("{}", e)))?;
+        writeln!(self.writer).map_err(|e| PumiceError::ReplayError(format!("{}", e)))
+    }
+
+    pub fn flush(&mut self) -> PumiceResult<()> {
+        self.writer
+            .flush()
+            .map_err(|e| PumiceError::ReplayError(format!("{}", e)))
+    }
+}
+
+/// Events and frame times loaded from a file written by `Recorder`, handed back out frame by
+/// frame.
+pub struct Replay {
+    events: VecDeque<(u64, Event)>,
+    delta_times: VecDeque<(u64, f32)>,
+    pub exit_when_finished: bool,
+}
+
+impl Replay {
+    pub fn load(path: impl AsRef<Path>) -> PumiceResult<Self> {
+        let file = File::open(path).map_err(|e| PumiceError::ReplayError(format!("{}", e)))?;
+
+        let mut events = VecDeque::new();
+        let mut delta_times = VecDeque::new();
+        for (line_num, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|e| PumiceError::ReplayError(format!("{}", e)))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let recorded: RecordedLine = serde_json::from_str(&line)
+                .map_err(|e| PumiceError::ReplayError(format!("line {}: {}", line_num + 1, e)))?;
+            if let Some(event) = recorded.event {
+                events.push_back((recorded.frame, event.into_event()));
+            }
+            if let Some(delta_time) = recorded.delta_time {
+                delta_times.push_back((recorded.frame, delta_time));
+            }
+        }
+
+        Ok(Replay {
+            events,
+            delta_times,
+            exit_when_finished: false,
+        })
+    }
+
+    pub fn events_for_frame(&mut self, frame: u64) -> Vec<Event> {
+        let mut events = Vec::new();
+        while let Some((event_frame, _)) = self.events.front() {
+            if *event_frame > frame {
+                break;
+            }
+            events.push(self.events.pop_front().unwrap().1);
+        }
+        events
+    }
+
+    // the recorded delta time for `frame`, dropping any for frames that were skipped
+    pub fn delta_time_for_frame(&mut self, frame: u64) -> Option<f32> {
+        while let Some(&(delta_time_frame, delta_time)) = self.delta_times.front() {
+            if delta_time_frame > frame {
+                break;
+            }
+            self.delta_times.pop_front();
+            if delta_time_frame == frame {
+                return Some(delta_time);
+            }
+        }
+        None
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.events.is_empty() && self.delta_times.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gamepad::{Button, GamepadId};
+
+    fn window_event(event: WindowEvent) -> Event {
+        Event::Winit(winit::Event::WindowEvent {
+            window_id: unsafe { WindowId::dummy() },
+            event,
+        })
+    }
+
+    fn key(state: ElementState) -> KeyboardInput {
+        KeyboardInput {
+            scancode: 57,
+            state,
+            virtual_keycode: Some(winit::VirtualKeyCode::Space),
+            modifiers: ModifiersState::default(),
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("pumice-{}-{}.jsonl", name, std::process::id()))
+    }
+
+    #[test]
+    fn round_trip() {
+        let device_id = unsafe { DeviceId::dummy() };
+        let first = vec![
+            window_event(WindowEvent::ReceivedCharacter('a')),
+            window_event(WindowEvent::KeyboardInput {
+                device_id,
+                input: key(ElementState::Pressed),
+            }),
+            Event::Gamepad(GamepadEvent::ButtonPressed(GamepadId(1), Button::South)),
+        ];
+        let second = vec![
+            window_event(WindowEvent::Resized(LogicalSize::new(800.0, 600.0))),
+            Event::Winit(winit::Event::DeviceEvent {
+                device_id,
+                event: DeviceEvent::MouseMotion { delta: (1.5, -2.0) },
+            }),
+            window_event(WindowEvent::Touch(Touch {
+                device_id,
+                phase: TouchPhase::Moved,
+                location: LogicalPosition::new(3.0, 4.0),
+                id: 9,
+            })),
+        ];
+
+        let path = temp_path("round-trip");
+        let mut recorder = Recorder::create(&path).unwrap();
+        recorder.record_delta_time(0, 0.016).unwrap();
+        for event in &first {
+            recorder.record(0, event).unwrap();
+        }
+        recorder.record_delta_time(1, 0.02).unwrap();
+        for event in &second {
+            recorder.record(1, event).unwrap();
+        }
+        recorder.flush().unwrap();
+
+        let mut replay = Replay::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(replay.delta_time_for_frame(0), Some(0.016));
+        assert_eq!(replay.events_for_frame(0), first);
+        assert_eq!(replay.delta_time_for_frame(1), Some(0.02));
+        assert_eq!(replay.events_for_frame(1), second);
+        assert!(replay.is_finished());
+    }
+
+    #[test]
+    fn events_are_keyed_by_frame() {
+        let path = temp_path("frames");
+        let mut recorder = Recorder::create(&path).unwrap();
+        for &frame in &[0, 2, 2, 5] {
+            recorder.record_delta_time(frame, frame as f32).unwrap();
+            let event = window_event(WindowEvent::ReceivedCharacter((b'a' + frame as u8) as char));
+            recorder.record(frame, &event).unwrap();
+        }
+        recorder.flush().unwrap();
+        let mut replay = Replay::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(replay.events_for_frame(0).len(), 1);
+        assert!(replay.events_for_frame(1).is_empty());
+        assert_eq!(replay.delta_time_for_frame(1), None);
+        assert_eq!(replay.events_for_frame(2).len(), 2);
+        // frames the replay missed are caught up on the next one
+        assert_eq!(
+            replay.events_for_frame(7),
+            vec![window_event(WindowEvent::ReceivedCharacter('f'))]
+        );
+        assert!(!replay.is_finished());
+        assert_eq!(replay.delta_time_for_frame(6), None);
+        assert!(replay.is_finished());
+    }
+
+    #[test]
+    fn bad_lines_report_their_line_number() {
+        let path = temp_path("bad-line");
+        std::fs::write(&path, "\n{\"frame\": 0}\nnot json\n").unwrap();
+        let result = Replay::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        match result {
+            Err(PumiceError::ReplayError(message)) => assert!(message.starts_with("line 3")),
+            _ => panic!("expected a replay error"),
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::mem::{self, Discriminant};
+
+use crate::error::PumiceError;
+
+// where in `run` an error came from
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorOrigin {
+    Update,
+    HandleEvent,
+    Recording,
+    // a frame that couldn't be submitted, the next one is tried as normal
+    Render,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ErrorAction {
+    Continue,
+    // `run` returns the error
+    Stop,
+}
+
+// Gets every error along with how many times the same kind of error has happened so far, including
+// this one. Installed with `ContextBuilder::error_handler`.
+pub type ErrorHandler = Box<dyn FnMut(ErrorOrigin, &PumiceError, u32) -> ErrorAction>;
+
+// Without a handler errors go to the `log` crate. Errors that keep happening every frame are only
+// logged the 1st, 2nd, 4th, 8th... time so they don't drown out everything else.
+pub(crate) struct ErrorReporter {
+    handler: Option<ErrorHandler>,
+    // keyed on the variant rather than the message, so errors with a changing message, like a
+    // frame number, don't grow this forever
+    counts: HashMap<(ErrorOrigin, Discriminant<PumiceError>), u32>,
+}
+
+impl ErrorReporter {
+    pub(crate) fn new(handler: Option<ErrorHandler>) -> Self {
+        ErrorReporter {
+            handler,
+            counts: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn report(&mut self, origin: ErrorOrigin, err: &PumiceError) -> ErrorAction {
+        let count = self
+            .counts
+            .entry((origin, mem::discriminant(err)))
+            .or_insert(0);
+        *count += 1;
+        let count = *count;
+
+        match &mut self.handler {
+            Some(handler) => handler(origin, err, count),
+            None => {
+                if count.is_power_of_two() {
+                    if count == 1 {
+                        log::error!("{:?}: {}", origin, err);
+                    } else {
+                        log::error!("{:?}: {} (happened {} times)", origin, err, count);
+                    }
+                }
+                ErrorAction::Continue
+            }
+        }
+    }
+
+    // how many times errors from `origin` have been reported
+    pub(crate) fn count(&self, origin: ErrorOrigin) -> u32 {
+        self.counts
+            .iter()
+            .filter(|((o, _), _)| *o == origin)
+            .map(|(_, count)| count)
+            .sum()
+    }
+}
@@ -1,10 +1,23 @@
 #version 450
 
 layout(location = 0) in vec4 in_color;
+layout(location = 1) in vec4 gradient_color;
+layout(location = 2) in vec4 gradient;
+layout(location = 3) in float gradient_type;
+layout(location = 4) in vec2 local_position;
 layout(location = 0) out vec4 f_color;
 layout(origin_upper_left) in vec4 gl_FragCoord;
 
 void main() {
    /* f_color = vec4(1.0 - in_color.x, 1.0 - in_color.y, 1.0 - in_color.z, 1.0); */
-   f_color = in_color;
+   if (gradient_type > 1.5) {
+      float t = length(local_position - gradient.xy) / max(gradient.z, 0.00001);
+      f_color = mix(in_color, gradient_color, clamp(t, 0.0, 1.0));
+   } else if (gradient_type > 0.5) {
+      vec2 dir = gradient.zw - gradient.xy;
+      float t = dot(local_position - gradient.xy, dir) / max(dot(dir, dir), 0.00001);
+      f_color = mix(in_color, gradient_color, clamp(t, 0.0, 1.0));
+   } else {
+      f_color = in_color;
+   }
 }
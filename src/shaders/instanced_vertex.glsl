@@ -0,0 +1,35 @@
+#version 450
+
+// the mesh, shared by every instance
+layout(location = 0) in vec2 mesh_position;
+// per instance
+layout(location = 1) in vec2 position;
+layout(location = 2) in vec2 scale;
+layout(location = 3) in float rotation; // degrees, around `position`
+layout(location = 4) in vec4 color;
+layout(location = 0) out vec4 fragcolor;
+layout(location = 1) out vec4 frag_gradient_color;
+layout(location = 2) out vec4 frag_gradient;
+layout(location = 3) out float frag_gradient_type;
+layout(location = 4) out vec2 local_position;
+
+layout(set=0, binding=0) uniform Data {
+   vec2 scale;
+} uniforms;
+
+void main() {
+   float rad = radians(rotation);
+   float sin_ang = sin(rad);
+   float cos_ang = cos(rad);
+
+   vec2 p = mesh_position * scale;
+   p = vec2(cos_ang * p.x - sin_ang * p.y, sin_ang * p.x + cos_ang * p.y) + position;
+
+   gl_Position = vec4(p * uniforms.scale, 0.0, 1.0);
+   // instances are a flat color, the fragment shader is shared with everything else
+   fragcolor = color;
+   frag_gradient_color = color;
+   frag_gradient = vec4(0.0);
+   frag_gradient_type = 0.0;
+   local_position = p;
+}
@@ -3,27 +3,37 @@
 layout(location = 0) in vec2 position;
 layout(location = 1) in vec4 color;
 layout(location = 2) in vec3 rot; // [x, y, degrees]
+layout(location = 3) in vec4 gradient_color;
+layout(location = 4) in vec4 gradient; // linear: [start.xy, end.xy], radial: [center.xy, radius, 0]
+layout(location = 5) in float gradient_type; // 0 solid, 1 linear, 2 radial
 layout(location = 0) out vec4 fragcolor;
+layout(location = 1) out vec4 frag_gradient_color;
+layout(location = 2) out vec4 frag_gradient;
+layout(location = 3) out float frag_gradient_type;
+layout(location = 4) out vec2 local_position;
 
 layout(set=0, binding=0) uniform Data {
    vec2 scale;
 } uniforms;
 
 void main() {
-   float x = position.x;
-   float y = position.y;
-
    float rad = radians(rot.z);
 
    float sin_ang = sin(rad);
    float cos_ang = cos(rad);
 
-   x = cos_ang * (x - rot.x) - sin_ang * (y - rot.y) + rot.x;
-   y = sin_ang * (x - rot.x) + cos_ang * (y - rot.y) + rot.y;
+   // both from the unrotated position, y used to pick up the already rotated x
+   float x = cos_ang * (position.x - rot.x) - sin_ang * (position.y - rot.y) + rot.x;
+   float y = sin_ang * (position.x - rot.x) + cos_ang * (position.y - rot.y) + rot.y;
 
    x *= uniforms.scale.x;
    y *= uniforms.scale.y;
 
    gl_Position = vec4(x, y, 0.0, 1.0);
    fragcolor = color;
+   frag_gradient_color = gradient_color;
+   frag_gradient = gradient;
+   frag_gradient_type = gradient_type;
+   // gradients are worked out before rotation so they turn with the shape
+   local_position = position;
 }
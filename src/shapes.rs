@@ -0,0 +1,572 @@
+use std::f32::consts::PI;
+
+use lyon::math::{point, vector, Angle, Point};
+use lyon::path::Path;
+use lyon::tessellation::basic_shapes::{self, BorderRadii};
+use lyon::tessellation::math::{Rect, Size};
+use lyon::tessellation::{
+    BuffersBuilder, FillOptions, FillTessellator, StrokeOptions, StrokeTessellator,
+};
+
+use crate::error::{PumiceError, PumiceResult};
+use crate::{GraphicsContext, Paint, Rotation, WithPaintRotCenter};
+
+/// Whether a shape is filled in or only has its outline drawn.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Style {
+    Fill,
+    // outline with the given line width
+    Stroke(f32),
+}
+
+fn rot_center(rot: Option<Rotation>) -> [f32; 3] {
+    match rot {
+        Some(rot) => [rot.point[0], rot.point[1], rot.degrees],
+        None => [0.0, 0.0, 0.0],
+    }
+}
+
+// Angles are in degrees like `Rotation`, starting from the positive x axis and going clockwise on
+// screen. Enough points are used to stay within the tessellation tolerance.
+fn arc_points(center: Point, radius: f32, start: f32, sweep: f32, tolerance: f32) -> Vec<Point> {
+    let sweep_rad = sweep.to_radians();
+    let max_step = 2.0 * (1.0 - tolerance / radius.max(tolerance)).acos();
+    let segments = (sweep_rad.abs() / max_step.max(0.001))
+        .ceil()
+        .clamp(2.0, 256.0) as usize;
+
+    (0..=segments)
+        .map(|i| {
+            let angle = start.to_radians() + sweep_rad * i as f32 / segments as f32;
+            point(
+                center.x + radius * angle.cos(),
+                center.y + radius * angle.sin(),
+            )
+        })
+        .collect()
+}
+
+// lyon 0.15 has no fill_ellipse, so filled ellipses are tessellated as a polygon instead. The
+// points are spaced for the larger radius, which keeps them within the tolerance everywhere.
+pub(crate) fn ellipse_points(center: Point, radii: [f32; 2], tolerance: f32) -> Vec<Point> {
+    let radius = radii[0].max(radii[1]);
+    if radius.is_nan() || radius <= 0.0 {
+        return Vec::new();
+    }
+
+    let mut points = arc_points(point(0.0, 0.0), radius, 0.0, 360.0, tolerance);
+    // the last point is the first one again
+    points.pop();
+    points
+        .into_iter()
+        .map(|p| {
+            point(
+                center.x + p.x * radii[0] / radius,
+                center.y + p.y * radii[1] / radius,
+            )
+        })
+        .collect()
+}
+
+// corners of a regular polygon or star, `radii` alternating between points, first one straight up
+fn star_points(center: Point, radii: &[f32], corners: usize) -> Vec<Point> {
+    (0..corners)
+        .map(|i| {
+            let angle = -PI / 2.0 + 2.0 * PI * i as f32 / corners as f32;
+            let radius = radii[i % radii.len()];
+            point(
+                center.x + radius * angle.cos(),
+                center.y + radius * angle.sin(),
+            )
+        })
+        .collect()
+}
+
+impl GraphicsContext {
+    fn new_path(
+        &mut self,
+        points: &[Point],
+        closed: bool,
+        style: Style,
+        paint: Paint,
+        rot: Option<Rotation>,
+    ) -> PumiceResult<()> {
+        if points.len() < 2 {
+            return Ok(());
+        }
+
+        let mut path_builder = Path::builder();
+        path_builder.move_to(points[0]);
+        for &p in &points[1..] {
+            path_builder.line_to(p);
+        }
+        if closed {
+            path_builder.close();
+        }
+        let path = path_builder.build();
+
+        let tolerance = self.tolerance();
+        let first_index = self.geometry.indices.len();
+        let mut buffer_builder = BuffersBuilder::new(
+            &mut self.geometry,
+            WithPaintRotCenter(paint, rot_center(rot)),
+        );
+        let result = match style {
+            Style::Fill => FillTessellator::new().tessellate_with_ids(
+                path.id_iter(),
+                &path,
+                Some(&path),
+                &FillOptions::tolerance(tolerance),
+                &mut buffer_builder,
+            ),
+            Style::Stroke(width) => StrokeTessellator::new().tessellate_path(
+                &path,
+                &StrokeOptions::tolerance(tolerance).with_line_width(width),
+                &mut buffer_builder,
+            ),
+        };
+
+        match result {
+            Ok(_) => self.finish_shape(first_index, style),
+            Err(tesselate_error) => Err(PumiceError::from(tesselate_error)),
+        }
+    }
+
+    pub fn new_circle_outline(
+        &mut self,
+        pos: impl Into<Point>,
+        rad: f32,
+        line_width: f32,
+        paint: impl Into<Paint>,
+    ) -> PumiceResult<()> {
+        self.new_ellipse_full(pos, [rad, rad], Style::Stroke(line_width), paint, None)
+    }
+
+    pub fn new_rectangle_outline(
+        &mut self,
+        pos: impl Into<Point>,
+        sides: impl Into<Size>,
+        line_width: f32,
+        paint: impl Into<Paint>,
+    ) -> PumiceResult<()> {
+        self.new_rectangle_outline_full(pos, sides, line_width, paint, None)
+    }
+
+    pub fn new_rectangle_outline_full(
+        &mut self,
+        pos: impl Into<Point>,
+        sides: impl Into<Size>,
+        line_width: f32,
+        paint: impl Into<Paint>,
+        rot: Option<Rotation>,
+    ) -> PumiceResult<()> {
+        let tolerance = self.tolerance();
+        let first_index = self.geometry.indices.len();
+        let options = StrokeOptions::tolerance(tolerance).with_line_width(line_width);
+        let rect = Rect::new(pos.into(), sides.into());
+        let mut buffer_builder = BuffersBuilder::new(
+            &mut self.geometry,
+            WithPaintRotCenter(paint.into(), rot_center(rot)),
+        );
+        match basic_shapes::stroke_rectangle(&rect, &options, &mut buffer_builder) {
+            Ok(_) => self.finish_shape(first_index, Style::Stroke(line_width)),
+            Err(tesselate_error) => Err(PumiceError::from(tesselate_error)),
+        }
+    }
+
+    pub fn new_ellipse(
+        &mut self,
+        center: impl Into<Point>,
+        radii: [f32; 2],
+        paint: impl Into<Paint>,
+    ) -> PumiceResult<()> {
+        self.new_ellipse_full(center, radii, Style::Fill, paint, None)
+    }
+
+    pub fn new_ellipse_outline(
+        &mut self,
+        center: impl Into<Point>,
+        radii: [f32; 2],
+        line_width: f32,
+        paint: impl Into<Paint>,
+    ) -> PumiceResult<()> {
+        self.new_ellipse_full(center, radii, Style::Stroke(line_width), paint, None)
+    }
+
+    pub fn new_ellipse_full(
+        &mut self,
+        center: impl Into<Point>,
+        radii: [f32; 2],
+        style: Style,
+        paint: impl Into<Paint>,
+        rot: Option<Rotation>,
+    ) -> PumiceResult<()> {
+        let tolerance = self.tolerance();
+        let center = center.into();
+        let width = match style {
+            Style::Fill => {
+                let points = ellipse_points(center, radii, tolerance);
+                return self.new_path(&points, true, style, paint.into(), rot);
+            }
+            Style::Stroke(width) => width,
+        };
+
+        let first_index = self.geometry.indices.len();
+        let mut buffer_builder = BuffersBuilder::new(
+            &mut self.geometry,
+            WithPaintRotCenter(paint.into(), rot_center(rot)),
+        );
+        let result = basic_shapes::stroke_ellipse(
+            center,
+            vector(radii[0], radii[1]),
+            Angle::radians(0.0),
+            &StrokeOptions::tolerance(tolerance).with_line_width(width),
+            &mut buffer_builder,
+        );
+
+        match result {
+            Ok(_) => self.finish_shape(first_index, style),
+            Err(tesselate_error) => Err(PumiceError::from(tesselate_error)),
+        }
+    }
+
+    pub fn new_rounded_rectangle(
+        &mut self,
+        pos: impl Into<Point>,
+        sides: impl Into<Size>,
+        radius: f32,
+        paint: impl Into<Paint>,
+    ) -> PumiceResult<()> {
+        self.new_rounded_rectangle_full(pos, sides, radius, Style::Fill, paint, None)
+    }
+
+    pub fn new_rounded_rectangle_outline(
+        &mut self,
+        pos: impl Into<Point>,
+        sides: impl Into<Size>,
+        radius: f32,
+        line_width: f32,
+        paint: impl Into<Paint>,
+    ) -> PumiceResult<()> {
+        self.new_rounded_rectangle_full(pos, sides, radius, Style::Stroke(line_width), paint, None)
+    }
+
+    pub fn new_rounded_rectangle_full(
+        &mut self,
+        pos: impl Into<Point>,
+        sides: impl Into<Size>,
+        radius: f32,
+        style: Style,
+        paint: impl Into<Paint>,
+        rot: Option<Rotation>,
+    ) -> PumiceResult<()> {
+        let tolerance = self.tolerance();
+        let first_index = self.geometry.indices.len();
+        let rect = Rect::new(pos.into(), sides.into());
+        let radii = BorderRadii::new_all_same(radius);
+        let mut buffer_builder = BuffersBuilder::new(
+            &mut self.geometry,
+            WithPaintRotCenter(paint.into(), rot_center(rot)),
+        );
+
+        let result = match style {
+            Style::Fill => basic_shapes::fill_rounded_rectangle(
+                &rect,
+                &radii,
+                &FillOptions::tolerance(tolerance),
+                &mut buffer_builder,
+            ),
+            Style::Stroke(width) => basic_shapes::stroke_rounded_rectangle(
+                &rect,
+                &radii,
+                &StrokeOptions::tolerance(tolerance).with_line_width(width),
+                &mut buffer_builder,
+            ),
+        };
+
+        match result {
+            Ok(_) => self.finish_shape(first_index, style),
+            Err(tesselate_error) => Err(PumiceError::from(tesselate_error)),
+        }
+    }
+
+    // an open curve, so there's only an outline version
+    pub fn new_arc(
+        &mut self,
+        center: impl Into<Point>,
+        radius: f32,
+        start_angle: f32,
+        sweep_angle: f32,
+        line_width: f32,
+        paint: impl Into<Paint>,
+    ) -> PumiceResult<()> {
+        self.new_arc_full(
+            center,
+            radius,
+            start_angle,
+            sweep_angle,
+            line_width,
+            paint,
+            None,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_arc_full(
+        &mut self,
+        center: impl Into<Point>,
+        radius: f32,
+        start_angle: f32,
+        sweep_angle: f32,
+        line_width: f32,
+        paint: impl Into<Paint>,
+        rot: Option<Rotation>,
+    ) -> PumiceResult<()> {
+        let tolerance = self.tolerance();
+        let points = arc_points(center.into(), radius, start_angle, sweep_angle, tolerance);
+        self.new_path(&points, false, Style::Stroke(line_width), paint.into(), rot)
+    }
+
+    pub fn new_pie_slice(
+        &mut self,
+        center: impl Into<Point>,
+        radius: f32,
+        start_angle: f32,
+        sweep_angle: f32,
+        paint: impl Into<Paint>,
+    ) -> PumiceResult<()> {
+        self.new_pie_slice_full(
+            center,
+            radius,
+            start_angle,
+            sweep_angle,
+            Style::Fill,
+            paint,
+            None,
+        )
+    }
+
+    pub fn new_pie_slice_outline(
+        &mut self,
+        center: impl Into<Point>,
+        radius: f32,
+        start_angle: f32,
+        sweep_angle: f32,
+        line_width: f32,
+        paint: impl Into<Paint>,
+    ) -> PumiceResult<()> {
+        self.new_pie_slice_full(
+            center,
+            radius,
+            start_angle,
+            sweep_angle,
+            Style::Stroke(line_width),
+            paint,
+            None,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_pie_slice_full(
+        &mut self,
+        center: impl Into<Point>,
+        radius: f32,
+        start_angle: f32,
+        sweep_angle: f32,
+        style: Style,
+        paint: impl Into<Paint>,
+        rot: Option<Rotation>,
+    ) -> PumiceResult<()> {
+        let center = center.into();
+        let mut points = vec![center];
+        points.extend(arc_points(
+            center,
+            radius,
+            start_angle,
+            sweep_angle,
+            self.tolerance(),
+        ));
+        self.new_path(&points, true, style, paint.into(), rot)
+    }
+
+    // `radius` is to the corners, and the first corner points straight up
+    pub fn new_regular_polygon(
+        &mut self,
+        center: impl Into<Point>,
+        radius: f32,
+        sides: usize,
+        paint: impl Into<Paint>,
+    ) -> PumiceResult<()> {
+        self.new_regular_polygon_full(center, radius, sides, Style::Fill, paint, None)
+    }
+
+    pub fn new_regular_polygon_outline(
+        &mut self,
+        center: impl Into<Point>,
+        radius: f32,
+        sides: usize,
+        line_width: f32,
+        paint: impl Into<Paint>,
+    ) -> PumiceResult<()> {
+        self.new_regular_polygon_full(
+            center,
+            radius,
+            sides,
+            Style::Stroke(line_width),
+            paint,
+            None,
+        )
+    }
+
+    pub fn new_regular_polygon_full(
+        &mut self,
+        center: impl Into<Point>,
+        radius: f32,
+        sides: usize,
+        style: Style,
+        paint: impl Into<Paint>,
+        rot: Option<Rotation>,
+    ) -> PumiceResult<()> {
+        let points = star_points(center.into(), &[radius], sides.max(3));
+        self.new_path(&points, true, style, paint.into(), rot)
+    }
+
+    // `points` is how many tips the star has, the first one points straight up
+    pub fn new_star(
+        &mut self,
+        center: impl Into<Point>,
+        outer_radius: f32,
+        inner_radius: f32,
+        points: usize,
+        paint: impl Into<Paint>,
+    ) -> PumiceResult<()> {
+        self.new_star_full(
+            center,
+            outer_radius,
+            inner_radius,
+            points,
+            Style::Fill,
+            paint,
+            None,
+        )
+    }
+
+    pub fn new_star_outline(
+        &mut self,
+        center: impl Into<Point>,
+        outer_radius: f32,
+        inner_radius: f32,
+        points: usize,
+        line_width: f32,
+        paint: impl Into<Paint>,
+    ) -> PumiceResult<()> {
+        self.new_star_full(
+            center,
+            outer_radius,
+            inner_radius,
+            points,
+            Style::Stroke(line_width),
+            paint,
+            None,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_star_full(
+        &mut self,
+        center: impl Into<Point>,
+        outer_radius: f32,
+        inner_radius: f32,
+        points: usize,
+        style: Style,
+        paint: impl Into<Paint>,
+        rot: Option<Rotation>,
+    ) -> PumiceResult<()> {
+        let corners = star_points(
+            center.into(),
+            &[outer_radius, inner_radius],
+            points.max(2) * 2,
+        );
+        self.new_path(&corners, true, style, paint.into(), rot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Point, b: Point) -> bool {
+        (a - b).length() < 1e-5
+    }
+
+    #[test]
+    fn arc_point_counts() {
+        // a coarse tolerance still gives at least two segments
+        let points = arc_points(point(0.0, 0.0), 1.0, 0.0, 90.0, 10.0);
+        assert_eq!(points.len(), 3);
+
+        // a finer tolerance gives more points, and half the sweep about half as many
+        let full = arc_points(point(0.0, 0.0), 1.0, 0.0, 360.0, 0.001);
+        let half = arc_points(point(0.0, 0.0), 1.0, 0.0, 180.0, 0.001);
+        assert!(full.len() > 20);
+        assert!((half.len() as i32 - full.len() as i32 / 2).abs() <= 2);
+
+        // capped at 256 segments
+        let points = arc_points(point(0.0, 0.0), 1.0, 0.0, 360.0, 1e-9);
+        assert_eq!(points.len(), 257);
+    }
+
+    #[test]
+    fn arc_angles_are_clockwise_degrees() {
+        // y points down on screen, so going from the positive x axis to the positive y axis is
+        // clockwise
+        let points = arc_points(point(1.0, 2.0), 2.0, 0.0, 90.0, 0.01);
+        assert!(close(points[0], point(3.0, 2.0)));
+        assert!(close(*points.last().unwrap(), point(1.0, 4.0)));
+
+        // negative sweeps go the other way, from the left round the bottom to the right
+        let points = arc_points(point(0.0, 0.0), 1.0, 180.0, -180.0, 0.01);
+        assert!(close(points[0], point(-1.0, 0.0)));
+        assert!(close(points[points.len() / 2], point(0.0, 1.0)));
+        assert!(close(*points.last().unwrap(), point(1.0, 0.0)));
+
+        for p in &points {
+            assert!(((*p - point(0.0, 0.0)).length() - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn star_radii_alternate() {
+        let center = point(1.0, 1.0);
+        let points = star_points(center, &[2.0, 1.0], 10);
+        assert_eq!(points.len(), 10);
+        // the first tip points straight up
+        assert!(close(points[0], point(1.0, -1.0)));
+        for (i, p) in points.iter().enumerate() {
+            let expected = if i % 2 == 0 { 2.0 } else { 1.0 };
+            assert!(((*p - center).length() - expected).abs() < 1e-5);
+        }
+
+        // a single radius gives a regular polygon
+        let square = star_points(point(0.0, 0.0), &[1.0], 4);
+        assert_eq!(square.len(), 4);
+        assert!(close(square[0], point(0.0, -1.0)));
+        assert!(close(square[1], point(1.0, 0.0)));
+        assert!(close(square[2], point(0.0, 1.0)));
+        assert!(close(square[3], point(-1.0, 0.0)));
+    }
+
+    #[test]
+    fn ellipse_points_follow_the_radii() {
+        let points = ellipse_points(point(1.0, 0.0), [2.0, 1.0], 0.01);
+        assert!(points.len() > 8);
+        assert!(close(points[0], point(3.0, 0.0)));
+        assert!(!close(*points.last().unwrap(), points[0]));
+        for p in &points {
+            let (x, y) = ((p.x - 1.0) / 2.0, p.y);
+            assert!((x * x + y * y - 1.0).abs() < 1e-4);
+        }
+
+        assert!(ellipse_points(point(0.0, 0.0), [0.0, 0.0], 0.01).is_empty());
+    }
+}
@@ -0,0 +1,375 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use crate::collision::Aabb;
+
+// cell sizes are raised to at least this, so zero, negative and NaN sizes still give a grid
+const MIN_CELL_SIZE: f32 = 0.000_001;
+// cell coordinates are clamped to this either way, so far away or infinite bounds can't overflow
+// the ring arithmetic in `nearest`
+const CELL_LIMIT: f32 = (1 << 29) as f32;
+// entities covering more cells than this are kept in a list of their own that every query checks
+const MAX_CELLS: i64 = 64;
+
+fn distance_to(aabb: &Aabb, point: [f32; 2]) -> f32 {
+    let dx = (aabb.min[0] - point[0])
+        .max(point[0] - aabb.max[0])
+        .max(0.0);
+    let dy = (aabb.min[1] - point[1])
+        .max(point[1] - aabb.max[1])
+        .max(0.0);
+    (dx * dx + dy * dy).sqrt()
+}
+
+// touching counts here, it's only a first pass before the real shape test
+fn touches(a: &Aabb, b: &Aabb) -> bool {
+    a.min[0] <= b.max[0] && a.max[0] >= b.min[0] && a.min[1] <= b.max[1] && a.max[1] >= b.min[1]
+}
+
+/// Buckets entities into a grid of square cells by their bounds, so region and nearest queries
+/// only look at entities close by instead of all of them. IDs can be anything small and hashable,
+/// like an index into the game's own list of entities.
+///
+/// Pick a cell size around the size of a typical entity. Much bigger and each cell holds too much,
+/// much smaller and big entities are stored in lots of cells. The few that would cover more than
+/// 64 cells, like a level's floor, are kept aside and checked by every query instead.
+#[derive(Clone, Debug)]
+pub struct SpatialHash<T> {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<T>>,
+    oversized: Vec<T>,
+    bounds: HashMap<T, Aabb>,
+}
+
+impl<T: Copy + Eq + Hash> SpatialHash<T> {
+    // Sizes that aren't positive and finite are clamped, a tiny size just puts more entities in
+    // the oversized list.
+    pub fn new(cell_size: f32) -> Self {
+        SpatialHash {
+            cell_size: if cell_size > MIN_CELL_SIZE {
+                cell_size.min(f32::MAX)
+            } else {
+                MIN_CELL_SIZE
+            },
+            cells: HashMap::new(),
+            oversized: Vec::new(),
+            bounds: HashMap::new(),
+        }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    fn cell(&self, point: [f32; 2]) -> (i32, i32) {
+        // `as` turns NaN into 0
+        let coordinate =
+            |x: f32| (x / self.cell_size).floor().clamp(-CELL_LIMIT, CELL_LIMIT) as i32;
+        (coordinate(point[0]), coordinate(point[1]))
+    }
+
+    // the cells `aabb` covers, or None if there are more than `limit`
+    fn cells_in(&self, aabb: &Aabb, limit: i64) -> Option<impl Iterator<Item = (i32, i32)>> {
+        let min = self.cell(aabb.min);
+        let max = self.cell(aabb.max);
+        let columns = (i64::from(max.0) - i64::from(min.0) + 1).max(0);
+        let rows = (i64::from(max.1) - i64::from(min.1) + 1).max(0);
+        if columns * rows > limit {
+            return None;
+        }
+        Some((min.0..=max.0).flat_map(move |x| (min.1..=max.1).map(move |y| (x, y))))
+    }
+
+    // adds `id`, or moves it if it's already there
+    pub fn insert(&mut self, id: T, bounds: Aabb) {
+        self.remove(id);
+        match self
+            .cells_in(&bounds, MAX_CELLS)
+            .map(|cells| cells.collect::<Vec<_>>())
+        {
+            Some(cells) => {
+                for cell in cells {
+                    self.cells.entry(cell).or_default().push(id);
+                }
+            }
+            None => self.oversized.push(id),
+        }
+        self.bounds.insert(id, bounds);
+    }
+
+    pub fn remove(&mut self, id: T) -> Option<Aabb> {
+        let bounds = self.bounds.remove(&id)?;
+        match self
+            .cells_in(&bounds, MAX_CELLS)
+            .map(|cells| cells.collect::<Vec<_>>())
+        {
+            Some(cells) => {
+                for cell in cells {
+                    if let Some(ids) = self.cells.get_mut(&cell) {
+                        ids.retain(|&other| other != id);
+                        if ids.is_empty() {
+                            self.cells.remove(&cell);
+                        }
+                    }
+                }
+            }
+            None => self.oversized.retain(|&other| other != id),
+        }
+        Some(bounds)
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.oversized.clear();
+        self.bounds.clear();
+    }
+
+    pub fn bounds(&self, id: T) -> Option<&Aabb> {
+        self.bounds.get(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.bounds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bounds.is_empty()
+    }
+
+    // every entity whose bounds touch `region`, e.g. the screen for culling
+    pub fn query_region(&self, region: &Aabb) -> Vec<T> {
+        let mut seen = HashSet::new();
+        // a region covering more cells than are occupied is quicker to check against everything
+        let near = match self.cells_in(region, self.cells.len() as i64) {
+            Some(cells) => cells
+                .filter_map(|cell| self.cells.get(&cell))
+                .flatten()
+                .collect::<Vec<_>>(),
+            None => self.bounds.keys().collect(),
+        };
+        near.into_iter()
+            .chain(&self.oversized)
+            .filter(|&&id| seen.insert(id) && touches(&self.bounds[&id], region))
+            .cloned()
+            .collect()
+    }
+
+    pub fn query_point(&self, point: [f32; 2]) -> Vec<T> {
+        self.query_region(&Aabb {
+            min: point,
+            max: point,
+        })
+    }
+
+    // The entity whose bounds are closest to `point` and how far away they are, 0 if the point is
+    // inside them. Searches outwards a ring of cells at a time, so close entities are found fast.
+    pub fn nearest(&self, point: [f32; 2]) -> Option<(T, f32)> {
+        let mut best: Option<(T, f32)> = None;
+        let consider = |best: &mut Option<(T, f32)>, id: T| {
+            let distance = distance_to(&self.bounds[&id], point);
+            if best.is_none_or(|(_, best)| distance < best) {
+                *best = Some((id, distance));
+            }
+        };
+        for &id in &self.oversized {
+            consider(&mut best, id);
+        }
+
+        let center = self.cell(point);
+        // past this ring there's nothing left to find
+        let last_ring = self
+            .cells
+            .keys()
+            .map(|&(x, y)| (x - center.0).abs().max((y - center.1).abs()))
+            .max()
+            .unwrap_or(0);
+        for ring in 0..=last_ring {
+            // Each ring has more cells than the last, so once there are more rings than occupied
+            // cells it's quicker to check everything that's left.
+            if ring as usize > self.cells.len() {
+                for &id in self.cells.values().flatten() {
+                    consider(&mut best, id);
+                }
+                break;
+            }
+
+            let ring_cells = (-ring..=ring).flat_map(|x| {
+                (-ring..=ring)
+                    .filter(move |&y| x.abs() == ring || y.abs() == ring)
+                    .map(move |y| (center.0 + x, center.1 + y))
+            });
+            for &id in ring_cells
+                .filter_map(|cell| self.cells.get(&cell))
+                .flatten()
+            {
+                consider(&mut best, id);
+            }
+
+            // anything in the next ring out is at least this far away
+            if let Some((_, distance)) = best {
+                if distance <= ring as f32 * self.cell_size {
+                    break;
+                }
+            }
+        }
+        best
+    }
+
+    // Every pair of entities whose bounds touch, each pair once. Run the exact shape test on these
+    // instead of on every pair.
+    pub fn pairs(&self) -> Vec<(T, T)> {
+        let mut seen = HashSet::new();
+        let mut pairs = Vec::new();
+        for ids in self.cells.values() {
+            for (i, &a) in ids.iter().enumerate() {
+                for &b in &ids[i + 1..] {
+                    if touches(&self.bounds[&a], &self.bounds[&b])
+                        && !seen.contains(&(b, a))
+                        && seen.insert((a, b))
+                    {
+                        pairs.push((a, b));
+                    }
+                }
+            }
+        }
+        for &a in &self.oversized {
+            for &b in self.bounds.keys() {
+                if a != b
+                    && touches(&self.bounds[&a], &self.bounds[&b])
+                    && !seen.contains(&(b, a))
+                    && seen.insert((a, b))
+                {
+                    pairs.push((a, b));
+                }
+            }
+        }
+        pairs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x: f32, y: f32) -> Aabb {
+        Aabb::new([x, y], [1.0, 1.0])
+    }
+
+    fn sorted(mut ids: Vec<u32>) -> Vec<u32> {
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn bad_cell_sizes_are_clamped() {
+        for &size in &[0.0, -1.0, f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            let mut hash = SpatialHash::new(size);
+            assert!(hash.cell_size() > 0.0 && hash.cell_size().is_finite());
+            hash.insert(1, square(0.0, 0.0));
+            hash.insert(2, square(5.0, 5.0));
+            assert_eq!(hash.query_point([0.5, 0.5]), vec![1]);
+            assert_eq!(hash.nearest([4.0, 4.0]).map(|(id, _)| id), Some(2));
+        }
+    }
+
+    #[test]
+    fn insert_moves_and_remove() {
+        let mut hash = SpatialHash::new(1.0);
+        hash.insert(1, square(0.0, 0.0));
+        hash.insert(1, square(10.0, 10.0));
+        assert_eq!(hash.len(), 1);
+        assert!(hash.query_point([0.5, 0.5]).is_empty());
+        assert_eq!(hash.query_point([10.5, 10.5]), vec![1]);
+
+        assert_eq!(hash.remove(1), Some(square(10.0, 10.0)));
+        assert_eq!(hash.remove(1), None);
+        assert!(hash.is_empty());
+        assert!(hash.query_point([10.5, 10.5]).is_empty());
+    }
+
+    #[test]
+    fn query_region() {
+        let mut hash = SpatialHash::new(1.0);
+        for i in 0..10 {
+            hash.insert(i, square(i as f32 * 2.0, 0.0));
+        }
+        assert_eq!(
+            sorted(hash.query_region(&Aabb::new([1.5, 0.0], [3.0, 1.0]))),
+            vec![1, 2]
+        );
+        // bigger than the grid, so it checks everything
+        let everywhere = Aabb::new([-1000.0, -1000.0], [2000.0, 2000.0]);
+        assert_eq!(sorted(hash.query_region(&everywhere)).len(), 10);
+    }
+
+    #[test]
+    fn huge_bounds_go_in_the_oversized_list() {
+        let mut hash = SpatialHash::new(1.0);
+        hash.insert(1, square(0.0, 0.0));
+        hash.insert(
+            2,
+            Aabb {
+                min: [-1e30, -1e30],
+                max: [1e30, 1e30],
+            },
+        );
+        hash.insert(
+            3,
+            Aabb {
+                min: [f32::NEG_INFINITY, 0.0],
+                max: [f32::INFINITY, 0.1],
+            },
+        );
+        assert_eq!(hash.oversized.len(), 2);
+        assert_eq!(sorted(hash.query_point([0.5, 0.05])), vec![1, 2, 3]);
+        assert_eq!(sorted(hash.query_point([1e20, 0.05])), vec![2, 3]);
+
+        let mut pairs = hash
+            .pairs()
+            .into_iter()
+            .map(|(a, b)| (a.min(b), a.max(b)))
+            .collect::<Vec<_>>();
+        pairs.sort();
+        assert_eq!(pairs, vec![(1, 2), (1, 3), (2, 3)]);
+
+        hash.remove(2);
+        hash.remove(3);
+        assert!(hash.oversized.is_empty());
+        assert!(hash.query_point([1e20, 0.05]).is_empty());
+    }
+
+    #[test]
+    fn pairs_are_listed_once() {
+        let mut hash = SpatialHash::new(1.0);
+        // these two share four cells
+        hash.insert(1, Aabb::new([0.5, 0.5], [1.0, 1.0]));
+        hash.insert(2, Aabb::new([0.8, 0.8], [1.0, 1.0]));
+        hash.insert(3, square(5.0, 5.0));
+        assert_eq!(hash.pairs().len(), 1);
+    }
+
+    #[test]
+    fn nearest() {
+        let mut hash = SpatialHash::new(1.0);
+        assert_eq!(hash.nearest([0.0, 0.0]), None);
+
+        hash.insert(1, square(0.0, 0.0));
+        hash.insert(2, square(4.0, 0.0));
+        assert_eq!(hash.nearest([0.5, 0.5]), Some((1, 0.0)));
+        assert_eq!(hash.nearest([3.0, 0.5]), Some((2, 1.0)));
+        // closer to 1's edge than 2's even though 2's cell is nearer the point's cell
+        let (id, distance) = hash.nearest([2.4, 0.5]).unwrap();
+        assert_eq!(id, 1);
+        assert!((distance - 1.4).abs() < 1e-5);
+    }
+
+    #[test]
+    fn nearest_far_away_stops() {
+        let mut hash = SpatialHash::new(0.001);
+        hash.insert(1, square(0.0, 0.0));
+        hash.insert(2, square(1e6, 1e6));
+        assert_eq!(hash.nearest([1e6, 1e6]), Some((2, 0.0)));
+        assert_eq!(hash.nearest([-1e7, 0.5]).map(|(id, _)| id), Some(1));
+        assert!(hash.nearest([f32::INFINITY, 0.5]).is_some());
+    }
+}
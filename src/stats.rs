@@ -0,0 +1,348 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::error::PumiceResult;
+use crate::GraphicsContext;
+
+// frames kept for the rolling average, percentiles and the overlay graph
+const HISTORY_LEN: usize = 120;
+
+/// Timings for the last frame `run` drew, plus rolling numbers over the last couple of seconds.
+#[derive(Clone, Debug, Default)]
+pub struct FrameStats {
+    // time since the previous frame started
+    pub frame_time: Duration,
+    // averaged over the frame history
+    pub fps: f64,
+    pub frame_time_p50: Duration,
+    pub frame_time_p95: Duration,
+    pub frame_time_p99: Duration,
+    // time spent in the game's update function
+    pub update_time: Duration,
+    // CPU time spent building and submitting the command buffer, not how long the GPU takes to
+    // draw it
+    pub submit_cpu_time: Duration,
+    pub vertices: usize,
+    pub indices: usize,
+    pub draw_calls: u32,
+}
+
+pub(crate) struct StatsCollector {
+    history: VecDeque<Duration>,
+    last_frame: Option<Instant>,
+    stats: FrameStats,
+}
+
+impl StatsCollector {
+    pub(crate) fn new() -> Self {
+        StatsCollector {
+            history: VecDeque::with_capacity(HISTORY_LEN),
+            last_frame: None,
+            stats: FrameStats::default(),
+        }
+    }
+
+    pub(crate) fn stats(&self) -> &FrameStats {
+        &self.stats
+    }
+
+    pub(crate) fn history(&self) -> impl Iterator<Item = &Duration> {
+        self.history.iter()
+    }
+
+    pub(crate) fn begin_frame(&mut self) {
+        let now = Instant::now();
+        if let Some(last_frame) = self.last_frame {
+            self.push_frame_time(now - last_frame);
+        }
+        self.last_frame = Some(now);
+    }
+
+    fn push_frame_time(&mut self, frame_time: Duration) {
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(frame_time);
+        self.stats.frame_time = frame_time;
+        self.update_rolling();
+    }
+
+    pub(crate) fn record_update(&mut self, update_time: Duration) {
+        self.stats.update_time = update_time;
+    }
+
+    pub(crate) fn record_submit(
+        &mut self,
+        submit_cpu_time: Duration,
+        vertices: usize,
+        indices: usize,
+        draw_calls: u32,
+    ) {
+        self.stats.submit_cpu_time = submit_cpu_time;
+        self.stats.vertices = vertices;
+        self.stats.indices = indices;
+        self.stats.draw_calls = draw_calls;
+    }
+
+    fn update_rolling(&mut self) {
+        let total: Duration = self.history.iter().sum();
+        self.stats.fps = self.history.len() as f64 / total.as_secs_f64().max(f64::EPSILON);
+
+        let mut sorted = self.history.iter().cloned().collect::<Vec<_>>();
+        sorted.sort();
+        let percentile = |p: f64| sorted[((sorted.len() - 1) as f64 * p).round() as usize];
+        self.stats.frame_time_p50 = percentile(0.5);
+        self.stats.frame_time_p95 = percentile(0.95);
+        self.stats.frame_time_p99 = percentile(0.99);
+    }
+}
+
+// which of the seven segments are lit for each digit, bits are a-g clockwise from the top with g
+// in the middle
+const DIGIT_SEGMENTS: [u8; 10] = [
+    0b0111111, 0b0000110, 0b1011011, 0b1001111, 0b1100110, 0b1101101, 0b1111101, 0b0000111,
+    0b1111111, 0b1101111,
+];
+
+fn draw_digit(
+    ctx: &mut GraphicsContext,
+    digit: usize,
+    pos: [f32; 2],
+    height: f32,
+    color: [f32; 4],
+) -> PumiceResult<()> {
+    let width = height / 2.0;
+    let thickness = height / 8.0;
+    let half = height / 2.0;
+    let [x, y] = pos;
+
+    // (x, y, w, h) of each segment, in the same order as the bits
+    let segments = [
+        (x, y, width, thickness),
+        (x + width - thickness, y, thickness, half),
+        (x + width - thickness, y + half, thickness, half),
+        (x, y + height - thickness, width, thickness),
+        (x, y + half, thickness, half),
+        (x, y, thickness, half),
+        (x, y + half - thickness / 2.0, width, thickness),
+    ];
+
+    for (i, &(sx, sy, sw, sh)) in segments.iter().enumerate() {
+        if DIGIT_SEGMENTS[digit] & (1 << i) != 0 {
+            ctx.new_rectangle([sx, sy], [sw, sh], color)?;
+        }
+    }
+    Ok(())
+}
+
+// 3x5 pixel capitals for the row labels, one byte per row with the leftmost pixel in the high bit
+fn letter_pixels(c: char) -> Option<[u8; 5]> {
+    Some(match c {
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'C' => [0b111, 0b100, 0b100, 0b100, 0b111],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b101, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        _ => return None,
+    })
+}
+
+// only the letters in `letter_pixels`, anything else is left as a space
+fn draw_label(
+    ctx: &mut GraphicsContext,
+    text: &str,
+    pos: [f32; 2],
+    height: f32,
+    color: [f32; 4],
+) -> PumiceResult<()> {
+    let pixel = height / 5.0;
+    let mut x = pos[0];
+    for c in text.chars() {
+        if let Some(rows) = letter_pixels(c) {
+            for (row, bits) in rows.iter().enumerate() {
+                for column in 0..3 {
+                    if bits & (0b100 >> column) != 0 {
+                        ctx.new_rectangle(
+                            [x + pixel * column as f32, pos[1] + pixel * row as f32],
+                            [pixel, pixel],
+                            color,
+                        )?;
+                    }
+                }
+            }
+        }
+        x += pixel * 4.0;
+    }
+    Ok(())
+}
+
+// digits and decimal points, anything else is skipped
+fn draw_number(
+    ctx: &mut GraphicsContext,
+    text: &str,
+    pos: [f32; 2],
+    height: f32,
+    color: [f32; 4],
+) -> PumiceResult<()> {
+    let advance = height * 0.7;
+    let mut x = pos[0];
+    for c in text.chars() {
+        if let Some(digit) = c.to_digit(10) {
+            draw_digit(ctx, digit as usize, [x, pos[1]], height, color)?;
+            x += advance;
+        } else if c == '.' {
+            let size = height / 8.0;
+            ctx.new_rectangle([x, pos[1] + height - size], [size, size], color)?;
+            x += advance / 2.0;
+        }
+    }
+    Ok(())
+}
+
+// The FPS in the top left corner with a graph of recent frame times under it. Bars are green when
+// the frame made the budget (the target fps, or 60) and red when it didn't; the line across the
+// graph is the budget. Under the graph, one per row in their own color and labelled: update and CPU
+// submit time in milliseconds, then the vertex, index and draw call counts.
+pub(crate) fn draw_overlay(ctx: &mut GraphicsContext) -> PumiceResult<()> {
+    let unit = ctx.screen_maxes[1] * 0.05;
+    let origin = [
+        -ctx.screen_maxes[0] + unit * 0.5,
+        -ctx.screen_maxes[1] + unit * 0.5,
+    ];
+    let graph_width = unit * 6.0;
+    let graph_height = unit * 2.0;
+    let bar_width = graph_width / HISTORY_LEN as f32;
+    let budget = 1.0 / ctx.target_fps().unwrap_or(60.0);
+
+    let stats = ctx.stats.stats().clone();
+    let history = ctx.stats.history().cloned().collect::<Vec<_>>();
+    let milliseconds = |time: Duration| format!("{:.2}", time.as_secs_f64() * 1000.0);
+    let rows = [
+        ("UPD", milliseconds(stats.update_time), [1.0, 0.8, 0.2, 1.0]),
+        (
+            "CPU",
+            milliseconds(stats.submit_cpu_time),
+            [0.3, 0.8, 1.0, 1.0],
+        ),
+        ("VTX", stats.vertices.to_string(), [0.8, 0.5, 1.0, 1.0]),
+        ("IDX", stats.indices.to_string(), [1.0, 0.5, 0.7, 1.0]),
+        ("DRAW", stats.draw_calls.to_string(), [0.6, 1.0, 0.6, 1.0]),
+    ];
+    let row_height = unit * 0.6;
+    let row_advance = unit * 0.8;
+    // room for four letters and a gap
+    let label_width = row_height * 3.6;
+
+    ctx.new_rectangle(
+        origin,
+        [
+            graph_width + unit * 0.5,
+            unit * 4.25 + row_advance * rows.len() as f32,
+        ],
+        [0.0, 0.0, 0.0, 1.0],
+    )?;
+    draw_number(
+        ctx,
+        &(stats.fps.round() as u32).to_string(),
+        [origin[0] + unit * 0.25, origin[1] + unit * 0.25],
+        unit,
+        [1.0, 1.0, 1.0, 1.0],
+    )?;
+
+    let graph_origin = [origin[0] + unit * 0.25, origin[1] + unit * 1.75];
+    let graph_bottom = graph_origin[1] + graph_height;
+    for (i, frame_time) in history.iter().enumerate() {
+        // the graph tops out at twice the budget
+        let fraction = (frame_time.as_secs_f64() / (budget * 2.0)).min(1.0) as f32;
+        let height = (graph_height * fraction).max(unit * 0.02);
+        let color = if frame_time.as_secs_f64() <= budget {
+            [0.2, 0.8, 0.2, 1.0]
+        } else {
+            [0.9, 0.2, 0.2, 1.0]
+        };
+        ctx.new_rectangle(
+            [
+                graph_origin[0] + bar_width * i as f32,
+                graph_bottom - height,
+            ],
+            [bar_width, height],
+            color,
+        )?;
+    }
+
+    ctx.new_rectangle(
+        [graph_origin[0], graph_bottom - graph_height / 2.0],
+        [graph_width, unit * 0.02],
+        [1.0, 1.0, 1.0, 1.0],
+    )?;
+
+    for (i, (label, text, color)) in rows.iter().enumerate() {
+        let y = graph_bottom + unit * 0.5 + row_advance * i as f32;
+        draw_label(ctx, label, [graph_origin[0], y], row_height, *color)?;
+        draw_number(
+            ctx,
+            text,
+            [graph_origin[0] + label_width, y],
+            row_height,
+            *color,
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn rolling_fps_and_percentiles() {
+        let mut collector = StatsCollector::new();
+        for i in 1..=100 {
+            collector.push_frame_time(ms(i));
+        }
+
+        let stats = collector.stats();
+        assert_eq!(stats.frame_time, ms(100));
+        // 100 frames in 5.05 seconds
+        assert!((stats.fps - 100.0 / 5.05).abs() < 1e-6);
+        assert_eq!(stats.frame_time_p50, ms(51));
+        assert_eq!(stats.frame_time_p95, ms(95));
+        assert_eq!(stats.frame_time_p99, ms(99));
+    }
+
+    #[test]
+    fn history_is_limited() {
+        let mut collector = StatsCollector::new();
+        for _ in 0..HISTORY_LEN {
+            collector.push_frame_time(ms(100));
+        }
+        assert!((collector.stats().fps - 10.0).abs() < 1e-6);
+
+        // the slow frames have all been pushed out
+        for _ in 0..HISTORY_LEN {
+            collector.push_frame_time(ms(10));
+        }
+        assert_eq!(collector.history().count(), HISTORY_LEN);
+        assert!((collector.stats().fps - 100.0).abs() < 1e-6);
+        assert_eq!(collector.stats().frame_time_p99, ms(10));
+    }
+
+    #[test]
+    fn label_letters_fit_in_three_columns() {
+        for c in "UPDCVTXIRAW".chars() {
+            let rows = letter_pixels(c).unwrap();
+            assert!(rows.iter().all(|&row| row < 0b1000), "{}", c);
+        }
+        assert_eq!(letter_pixels('?'), None);
+    }
+}
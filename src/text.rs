@@ -0,0 +1,127 @@
+// Text typed into the window, built from winit's ReceivedCharacter events so it follows the
+// user's keyboard layout. IME composition happens in the OS, the committed text shows up here.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextEvent {
+    Char(char),
+    Backspace,
+    Enter,
+}
+
+impl TextEvent {
+    pub fn from_char(c: char) -> Option<Self> {
+        match c {
+            '\u{8}' | '\u{7f}' => Some(TextEvent::Backspace),
+            '\r' | '\n' => Some(TextEvent::Enter),
+            c if c.is_control() => None,
+            c => Some(TextEvent::Char(c)),
+        }
+    }
+}
+
+/// A line of editable text, for things like name entry or a console prompt.
+#[derive(Clone, Debug, Default)]
+pub struct TextBuffer {
+    pub text: String,
+    pub max_len: Option<usize>,
+}
+
+impl TextBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_len(max_len: usize) -> Self {
+        TextBuffer {
+            text: String::new(),
+            max_len: Some(max_len),
+        }
+    }
+
+    // returns true if enter was pressed
+    pub fn apply(&mut self, events: &[TextEvent]) -> bool {
+        let mut submitted = false;
+        for event in events {
+            match event {
+                TextEvent::Char(c) => {
+                    if self
+                        .max_len
+                        .is_none_or(|max| self.text.chars().count() < max)
+                    {
+                        self.text.push(*c);
+                    }
+                }
+                TextEvent::Backspace => {
+                    self.text.pop();
+                }
+                TextEvent::Enter => submitted = true,
+            }
+        }
+        submitted
+    }
+
+    pub fn take(&mut self) -> String {
+        std::mem::take(&mut self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(text: &str) -> Vec<TextEvent> {
+        text.chars().filter_map(TextEvent::from_char).collect()
+    }
+
+    #[test]
+    fn from_char() {
+        assert_eq!(TextEvent::from_char('a'), Some(TextEvent::Char('a')));
+        assert_eq!(TextEvent::from_char('é'), Some(TextEvent::Char('é')));
+        assert_eq!(TextEvent::from_char(' '), Some(TextEvent::Char(' ')));
+        assert_eq!(TextEvent::from_char('\u{8}'), Some(TextEvent::Backspace));
+        assert_eq!(TextEvent::from_char('\u{7f}'), Some(TextEvent::Backspace));
+        assert_eq!(TextEvent::from_char('\r'), Some(TextEvent::Enter));
+        assert_eq!(TextEvent::from_char('\n'), Some(TextEvent::Enter));
+        // tab, escape and ctrl+c
+        assert_eq!(TextEvent::from_char('\t'), None);
+        assert_eq!(TextEvent::from_char('\u{1b}'), None);
+        assert_eq!(TextEvent::from_char('\u{3}'), None);
+    }
+
+    #[test]
+    fn typing_and_backspace() {
+        let mut buffer = TextBuffer::new();
+        assert!(!buffer.apply(&events("helx\u{8}lo")));
+        assert_eq!(buffer.text, "hello");
+
+        // backspace on an empty buffer does nothing
+        let mut buffer = TextBuffer::new();
+        buffer.apply(&events("\u{8}\u{8}a"));
+        assert_eq!(buffer.text, "a");
+    }
+
+    #[test]
+    fn max_len_counts_characters() {
+        let mut buffer = TextBuffer::with_max_len(3);
+        buffer.apply(&events("ééé"));
+        buffer.apply(&events("abc"));
+        assert_eq!(buffer.text, "ééé");
+
+        // there's room again after a backspace
+        buffer.apply(&events("\u{8}x"));
+        assert_eq!(buffer.text, "ééx");
+    }
+
+    #[test]
+    fn enter_submits_without_clearing() {
+        let mut buffer = TextBuffer::new();
+        assert!(buffer.apply(&events("hi\r")));
+        assert_eq!(buffer.text, "hi");
+        // events after enter in the same batch are still applied
+        assert!(buffer.apply(&events("\n!")));
+        assert_eq!(buffer.text, "hi!");
+
+        assert_eq!(buffer.take(), "hi!");
+        assert_eq!(buffer.text, "");
+        assert!(!buffer.apply(&[]));
+    }
+}
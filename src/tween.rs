@@ -0,0 +1,464 @@
+use std::f32::consts::PI;
+
+/// The standard easing curves, see easings.net for what each looks like. `In` eases at the start,
+/// `Out` at the end and `InOut` at both.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Easing {
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    // overshoots backwards before heading to the target, or past the target at the end
+    BackIn,
+    BackOut,
+    BackInOut,
+    ElasticIn,
+    ElasticOut,
+    ElasticInOut,
+    BounceIn,
+    BounceOut,
+    BounceInOut,
+}
+
+// how far the back easings overshoot
+const BACK: f32 = 1.70158;
+
+fn bounce_out(t: f32) -> f32 {
+    let n = 7.5625;
+    let d = 2.75;
+    if t < 1.0 / d {
+        n * t * t
+    } else if t < 2.0 / d {
+        let t = t - 1.5 / d;
+        n * t * t + 0.75
+    } else if t < 2.5 / d {
+        let t = t - 2.25 / d;
+        n * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / d;
+        n * t * t + 0.984_375
+    }
+}
+
+impl Easing {
+    // maps progress from 0 to 1 onto the curve, which is 0 at the start and 1 at the end but can
+    // go outside that in between
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => 1.0 - (1.0 - t) * (1.0 - t),
+            Easing::QuadInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(2) / 2.0
+                }
+            }
+            Easing::CubicIn => t * t * t,
+            Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Easing::CubicInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+            Easing::BackIn => (BACK + 1.0) * t * t * t - BACK * t * t,
+            Easing::BackOut => {
+                let t = t - 1.0;
+                1.0 + (BACK + 1.0) * t * t * t + BACK * t * t
+            }
+            Easing::BackInOut => {
+                let back = BACK * 1.525;
+                if t < 0.5 {
+                    (2.0 * t).powi(2) * ((back + 1.0) * 2.0 * t - back) / 2.0
+                } else {
+                    ((2.0 * t - 2.0).powi(2) * ((back + 1.0) * (2.0 * t - 2.0) + back) + 2.0) / 2.0
+                }
+            }
+            Easing::ElasticIn | Easing::ElasticOut | Easing::ElasticInOut
+                if t <= 0.0 || t >= 1.0 =>
+            {
+                t
+            }
+            Easing::ElasticIn => {
+                -(2.0f32.powf(10.0 * t - 10.0)) * ((10.0 * t - 10.75) * 2.0 * PI / 3.0).sin()
+            }
+            Easing::ElasticOut => {
+                2.0f32.powf(-10.0 * t) * ((10.0 * t - 0.75) * 2.0 * PI / 3.0).sin() + 1.0
+            }
+            Easing::ElasticInOut => {
+                let wave = ((20.0 * t - 11.125) * 2.0 * PI / 4.5).sin();
+                if t < 0.5 {
+                    -(2.0f32.powf(20.0 * t - 10.0) * wave) / 2.0
+                } else {
+                    2.0f32.powf(-20.0 * t + 10.0) * wave / 2.0 + 1.0
+                }
+            }
+            Easing::BounceIn => 1.0 - bounce_out(1.0 - t),
+            Easing::BounceOut => bounce_out(t),
+            Easing::BounceInOut => {
+                if t < 0.5 {
+                    (1.0 - bounce_out(1.0 - 2.0 * t)) / 2.0
+                } else {
+                    (1.0 + bounce_out(2.0 * t - 1.0)) / 2.0
+                }
+            }
+        }
+    }
+}
+
+/// Anything a `Tween` can move between: positions, sizes, colors and rotations are all `f32`s or
+/// arrays of them.
+pub trait Tweenable: Copy {
+    fn lerp(self, to: Self, t: f32) -> Self;
+}
+
+impl Tweenable for f32 {
+    fn lerp(self, to: Self, t: f32) -> Self {
+        self + (to - self) * t
+    }
+}
+
+macro_rules! impl_tweenable_array {
+    ($($n:expr),*) => {
+        $(
+            impl Tweenable for [f32; $n] {
+                fn lerp(mut self, to: Self, t: f32) -> Self {
+                    for (a, b) in self.iter_mut().zip(to.iter()) {
+                        *a = a.lerp(*b, t);
+                    }
+                    self
+                }
+            }
+        )*
+    };
+}
+
+impl_tweenable_array!(2, 3, 4);
+
+/// How many times a tween plays through its steps.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Repeat {
+    Once,
+    Times(u32),
+    Forever,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Step<T> {
+    // time spent holding the previous value before this step starts moving
+    delay: f32,
+    to: T,
+    duration: f32,
+    easing: Easing,
+}
+
+/// Moves a value through a sequence of steps over time. Build it with `to`, `wait` and the other
+/// builder methods, then call `update` with `ctx.delta_time()` every frame and read `value`.
+pub struct Tween<T: Tweenable> {
+    from: T,
+    steps: Vec<Step<T>>,
+    // added to the next step
+    pending_delay: f32,
+    repeat: Repeat,
+    // plays every other repeat backwards
+    yoyo: bool,
+    on_complete: Option<Box<dyn FnMut()>>,
+    // time into the current play through
+    elapsed: f32,
+    // play throughs finished so far
+    plays: u32,
+    finished: bool,
+    value: T,
+}
+
+impl<T: Tweenable> Tween<T> {
+    pub fn new(from: T) -> Self {
+        Tween {
+            from,
+            steps: Vec::new(),
+            pending_delay: 0.0,
+            repeat: Repeat::Once,
+            yoyo: false,
+            on_complete: None,
+            elapsed: 0.0,
+            plays: 0,
+            finished: false,
+            value: from,
+        }
+    }
+
+    // moves to `to` over `duration` seconds after the previous step
+    pub fn to(mut self, to: T, duration: f32, easing: Easing) -> Self {
+        self.steps.push(Step {
+            delay: self.pending_delay,
+            to,
+            duration,
+            easing,
+        });
+        self.pending_delay = 0.0;
+        self
+    }
+
+    // holds the current value for `seconds` before the next step, or before the tween finishes
+    pub fn wait(mut self, seconds: f32) -> Self {
+        self.pending_delay += seconds;
+        self
+    }
+
+    // waits `seconds` before the first step
+    pub fn delay(mut self, seconds: f32) -> Self {
+        match self.steps.first_mut() {
+            Some(step) => step.delay += seconds,
+            None => self.pending_delay += seconds,
+        }
+        self
+    }
+
+    pub fn repeat(mut self, repeat: Repeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    // runs every other play through backwards, so `Repeat::Times(2)` goes there and back
+    pub fn yoyo(mut self, yoyo: bool) -> Self {
+        self.yoyo = yoyo;
+        self
+    }
+
+    // called once, on the update the tween finishes on; never for `Repeat::Forever`
+    pub fn on_complete(mut self, f: impl FnMut() + 'static) -> Self {
+        self.on_complete = Some(Box::new(f));
+        self
+    }
+
+    pub fn value(&self) -> T {
+        self.value
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    // length of one play through, in seconds
+    pub fn duration(&self) -> f32 {
+        self.steps
+            .iter()
+            .map(|step| step.delay + step.duration)
+            .sum::<f32>()
+            + self.pending_delay
+    }
+
+    // starts over from the first step
+    pub fn restart(&mut self) {
+        self.elapsed = 0.0;
+        self.plays = 0;
+        self.finished = false;
+        self.value = self.from;
+    }
+
+    // advances by `dt` seconds and returns the new value
+    pub fn update(&mut self, dt: f32) -> T {
+        if self.finished {
+            return self.value;
+        }
+
+        let duration = self.duration();
+        self.elapsed += dt;
+        while self.elapsed >= duration {
+            let plays = self.plays + 1;
+            let done = match self.repeat {
+                Repeat::Once => true,
+                Repeat::Times(times) => plays >= times,
+                // a zero length tween would loop forever without getting anywhere
+                Repeat::Forever => duration <= 0.0,
+            };
+            if done {
+                self.elapsed = duration;
+                self.finished = true;
+                break;
+            }
+            self.plays = plays;
+            self.elapsed -= duration;
+        }
+
+        let backwards = self.yoyo && self.plays % 2 == 1;
+        let time = if backwards {
+            duration - self.elapsed
+        } else {
+            self.elapsed
+        };
+        self.value = self.sample(time);
+
+        if self.finished {
+            if let Some(on_complete) = self.on_complete.as_mut() {
+                on_complete();
+            }
+        }
+        self.value
+    }
+
+    fn sample(&self, mut time: f32) -> T {
+        let mut from = self.from;
+        for step in &self.steps {
+            if time < step.delay {
+                return from;
+            }
+            time -= step.delay;
+            if time < step.duration {
+                return from.lerp(step.to, step.easing.apply(time / step.duration));
+            }
+            time -= step.duration;
+            from = step.to;
+        }
+        from
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASINGS: [Easing; 16] = [
+        Easing::Linear,
+        Easing::QuadIn,
+        Easing::QuadOut,
+        Easing::QuadInOut,
+        Easing::CubicIn,
+        Easing::CubicOut,
+        Easing::CubicInOut,
+        Easing::BackIn,
+        Easing::BackOut,
+        Easing::BackInOut,
+        Easing::ElasticIn,
+        Easing::ElasticOut,
+        Easing::ElasticInOut,
+        Easing::BounceIn,
+        Easing::BounceOut,
+        Easing::BounceInOut,
+    ];
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn easings_start_at_0_and_end_at_1() {
+        for &easing in &EASINGS {
+            assert!(close(easing.apply(0.0), 0.0), "{:?} at 0", easing);
+            assert!(close(easing.apply(1.0), 1.0), "{:?} at 1", easing);
+            // progress outside 0 to 1 is clamped
+            assert!(close(easing.apply(-1.0), 0.0), "{:?} below 0", easing);
+            assert!(close(easing.apply(2.0), 1.0), "{:?} above 1", easing);
+        }
+    }
+
+    #[test]
+    fn in_out_easings_are_halfway_at_the_middle() {
+        for &easing in &[
+            Easing::Linear,
+            Easing::QuadInOut,
+            Easing::CubicInOut,
+            Easing::BackInOut,
+            Easing::ElasticInOut,
+            Easing::BounceInOut,
+        ] {
+            assert!(close(easing.apply(0.5), 0.5), "{:?}", easing);
+        }
+    }
+
+    #[test]
+    fn steps_run_in_order() {
+        let mut tween = Tween::new(0.0)
+            .to(1.0, 1.0, Easing::Linear)
+            .to(3.0, 1.0, Easing::Linear);
+        assert!(close(tween.update(0.5), 0.5));
+        assert!(close(tween.update(1.0), 2.0));
+        assert!(!tween.is_finished());
+        assert!(close(tween.update(1.0), 3.0));
+        assert!(tween.is_finished());
+    }
+
+    #[test]
+    fn wait_holds_the_value_between_steps() {
+        let mut tween =
+            Tween::new(0.0)
+                .to(1.0, 1.0, Easing::Linear)
+                .wait(1.0)
+                .to(2.0, 1.0, Easing::Linear);
+        assert!(close(tween.duration(), 3.0));
+        assert!(close(tween.update(1.5), 1.0));
+        assert!(close(tween.update(0.4), 1.0));
+        assert!(close(tween.update(0.6), 1.5));
+    }
+
+    #[test]
+    fn trailing_wait_delays_finishing() {
+        let mut tween = Tween::new(0.0).to(1.0, 1.0, Easing::Linear).wait(1.0);
+        tween.update(1.5);
+        assert!(!tween.is_finished());
+        assert!(close(tween.value(), 1.0));
+        tween.update(0.5);
+        assert!(tween.is_finished());
+    }
+
+    #[test]
+    fn delay_holds_the_start_value() {
+        let mut tween = Tween::new(0.0).to(1.0, 1.0, Easing::Linear).delay(0.5);
+        assert!(close(tween.update(0.4), 0.0));
+        assert!(close(tween.update(0.6), 0.5));
+        assert!(close(tween.update(0.5), 1.0));
+        assert!(tween.is_finished());
+
+        // delay before any steps applies to the first one
+        let mut tween = Tween::new(0.0).delay(1.0).to(1.0, 1.0, Easing::Linear);
+        assert!(close(tween.update(1.5), 0.5));
+    }
+
+    #[test]
+    fn repeat_times_plays_that_many_times() {
+        let count = std::rc::Rc::new(std::cell::Cell::new(0));
+        let completed = count.clone();
+        let mut tween = Tween::new(0.0)
+            .to(1.0, 1.0, Easing::Linear)
+            .repeat(Repeat::Times(3))
+            .on_complete(move || completed.set(completed.get() + 1));
+
+        assert!(close(tween.update(1.25), 0.25));
+        assert!(close(tween.update(1.0), 0.25));
+        assert!(!tween.is_finished());
+        assert!(close(tween.update(1.0), 1.0));
+        assert!(tween.is_finished());
+        tween.update(1.0);
+        assert_eq!(count.get(), 1);
+    }
+
+    #[test]
+    fn yoyo_plays_every_other_repeat_backwards() {
+        let mut tween = Tween::new(0.0)
+            .to(1.0, 1.0, Easing::Linear)
+            .repeat(Repeat::Times(2))
+            .yoyo(true);
+        assert!(close(tween.update(0.25), 0.25));
+        assert!(close(tween.update(1.0), 0.75));
+        assert!(close(tween.update(0.5), 0.25));
+        assert!(close(tween.update(0.5), 0.0));
+        assert!(tween.is_finished());
+    }
+
+    #[test]
+    fn forever_never_finishes() {
+        let mut tween = Tween::new([0.0, 0.0])
+            .to([2.0, 4.0], 1.0, Easing::Linear)
+            .repeat(Repeat::Forever);
+        let value = tween.update(10.5);
+        assert!(close(value[0], 1.0) && close(value[1], 2.0));
+        assert!(!tween.is_finished());
+    }
+}